@@ -0,0 +1,268 @@
+use std::marker::PhantomData;
+
+use aes_gcm::{
+    Aes256Gcm,
+    aead::{
+        Aead, AeadInPlace, Error, Key, KeyInit, Nonce, OsRng, Payload, Tag,
+        generic_array::typenum::Unsigned, rand_core::RngCore,
+    },
+};
+use chacha20poly1305::XChaCha20Poly1305;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::{
+    adapters::{kdf, recipient},
+    domain::{
+        errors::CryptoError,
+        models::{CipherSuite, Credentials, EntryId, KdfParams},
+        ports::CryptoPort,
+        secure_memory::SecureBuffer,
+    },
+};
+
+/* =======================
+   CIPHERS
+======================= */
+
+// An AEAD a vault can be encrypted with, and the suite naming it in the header
+pub trait SuiteCipher: AeadInPlace + KeyInit {
+    const SUITE: CipherSuite;
+}
+
+impl SuiteCipher for Aes256Gcm {
+    const SUITE: CipherSuite = CipherSuite::Aes256Gcm;
+}
+
+impl SuiteCipher for XChaCha20Poly1305 {
+    const SUITE: CipherSuite = CipherSuite::XChaCha20Poly1305;
+}
+
+pub type AesGcmCrypto = AeadCrypto<Aes256Gcm>;
+pub type XChaCha20Poly1305Crypto = AeadCrypto<XChaCha20Poly1305>;
+
+/* =======================
+   ADAPTER
+======================= */
+
+#[derive(ZeroizeOnDrop)]
+pub struct AeadCrypto<A: SuiteCipher> {
+    // Data key, in locked memory
+    key: Option<SecureBuffer>,
+    #[zeroize(skip)]
+    cipher: PhantomData<A>,
+}
+
+impl<A: SuiteCipher> AeadCrypto<A> {
+    pub fn new() -> Self {
+        Self {
+            key: None,
+            cipher: PhantomData,
+        }
+    }
+
+    fn key(&self) -> Result<&[u8; 32], CryptoError> {
+        let key = self.key.as_deref().ok_or(CryptoError::NotInitialized)?;
+        key.try_into().map_err(|_| CryptoError::InvalidKey)
+    }
+}
+
+impl<A: SuiteCipher> CryptoPort for AeadCrypto<A> {
+    fn select(&mut self, suite: CipherSuite) -> Result<(), CryptoError> {
+        if suite == A::SUITE {
+            Ok(())
+        } else {
+            Err(CryptoError::UnsupportedSuite(suite))
+        }
+    }
+
+    fn init(
+        &mut self,
+        creds: &Credentials,
+        salt: &[u8],
+        kdf: &KdfParams,
+    ) -> Result<(), CryptoError> {
+        let mut key = kdf::derive_key(creds, salt, kdf)?;
+        self.key = Some(SecureBuffer::from_slice(&key));
+        key.zeroize();
+        Ok(())
+    }
+
+    fn generate_key(&mut self) -> Result<(), CryptoError> {
+        let mut key = SecureBuffer::new(32);
+        OsRng.fill_bytes(&mut key);
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn wrap_key(
+        &self,
+        creds: &Credentials,
+        salt: &[u8],
+        kdf: &KdfParams,
+    ) -> Result<Vec<u8>, CryptoError> {
+        let key = self.key()?;
+        let mut kek = kdf::derive_key(creds, salt, kdf)?;
+        let sealed = seal::<A>(&kek, key, &[]);
+        kek.zeroize();
+
+        let (wrapped, nonce) = sealed?;
+        Ok([nonce.as_slice(), &wrapped].concat())
+    }
+
+    fn unwrap_key(
+        &mut self,
+        wrapped: &[u8],
+        creds: &Credentials,
+        salt: &[u8],
+        kdf: &KdfParams,
+    ) -> Result<(), CryptoError> {
+        let (nonce, wrapped) = wrapped
+            .split_at_checked(A::NonceSize::USIZE)
+            .ok_or(CryptoError::InvalidNonce)?;
+        let mut kek = kdf::derive_key(creds, salt, kdf)?;
+        let opened = open::<A>(&kek, wrapped, nonce, &[]);
+        kek.zeroize();
+
+        let key = opened?;
+        if key.len() != 32 {
+            return Err(CryptoError::InvalidKey);
+        }
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn generate_identity(&self) -> ([u8; 32], [u8; 32]) {
+        recipient::generate_identity()
+    }
+
+    fn wrap_key_for(&self, public_key: &[u8; 32]) -> Result<Vec<u8>, CryptoError> {
+        let key = self.key()?;
+        let (ephemeral, mut kek) = recipient::sender_key(public_key)?;
+        let sealed = seal::<A>(&kek, key, &[]);
+        kek.zeroize();
+
+        let (wrapped, nonce) = sealed?;
+        Ok([ephemeral.as_slice(), &nonce, &wrapped].concat())
+    }
+
+    fn unwrap_key_with(&mut self, wrapped: &[u8], secret: &[u8; 32]) -> Result<(), CryptoError> {
+        let (ephemeral, wrapped) = wrapped
+            .split_first_chunk::<32>()
+            .ok_or(CryptoError::InvalidPublicKey)?;
+        let (nonce, wrapped) = wrapped
+            .split_at_checked(A::NonceSize::USIZE)
+            .ok_or(CryptoError::InvalidNonce)?;
+        let mut kek = recipient::recipient_key(secret, ephemeral)?;
+        let opened = open::<A>(&kek, wrapped, nonce, &[]);
+        kek.zeroize();
+
+        let key = opened?;
+        if key.len() != 32 {
+            return Err(CryptoError::InvalidKey);
+        }
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<(Vec<u8>, Vec<u8>), CryptoError> {
+        let key = self.key()?;
+        seal::<A>(key, plaintext, aad)
+    }
+
+    fn decrypt(
+        &self,
+        ciphertext: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+    ) -> Result<SecureBuffer, CryptoError> {
+        let key = self.key()?;
+        open::<A>(key, ciphertext, nonce, aad)
+    }
+
+    fn encrypt_entry(
+        &self,
+        id: EntryId,
+        plaintext: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), CryptoError> {
+        let key = self.key()?;
+        let mut subkey = kdf::entry_key(key, id)?;
+        let sealed = seal::<A>(&subkey, plaintext, &[]);
+        subkey.zeroize();
+        sealed
+    }
+
+    fn decrypt_entry(
+        &self,
+        id: EntryId,
+        ciphertext: &[u8],
+        nonce: &[u8],
+    ) -> Result<SecureBuffer, CryptoError> {
+        let key = self.key()?;
+        let mut subkey = kdf::entry_key(key, id)?;
+        let opened = open::<A>(&subkey, ciphertext, nonce, &[]);
+        subkey.zeroize();
+        opened
+    }
+
+    fn clear(&mut self) {
+        self.key.zeroize();
+    }
+
+    fn salt_gen(&self) -> [u8; 16] {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        salt
+    }
+}
+
+/* =======================
+   SEAL / OPEN
+======================= */
+
+fn seal<A: SuiteCipher>(
+    key: &[u8; 32],
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<(Vec<u8>, Vec<u8>), CryptoError> {
+    let cipher = A::new(Key::<A>::from_slice(key));
+    let nonce = A::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|e| CryptoError::Aead(e.to_string()))?;
+    Ok((ciphertext, nonce.to_vec()))
+}
+
+fn open<A: SuiteCipher>(
+    key: &[u8; 32],
+    ciphertext: &[u8],
+    nonce: &[u8],
+    aad: &[u8],
+) -> Result<SecureBuffer, CryptoError> {
+    let cipher = A::new(Key::<A>::from_slice(key));
+    if nonce.len() != A::NonceSize::USIZE {
+        return Err(CryptoError::InvalidNonce);
+    }
+    let (ciphertext, tag) = ciphertext
+        .len()
+        .checked_sub(A::TagSize::USIZE)
+        .map(|at| ciphertext.split_at(at))
+        .ok_or_else(|| CryptoError::Aead(Error.to_string()))?;
+
+    // Decrypted in place, so the plaintext never leaves locked memory
+    let mut plaintext = SecureBuffer::from_slice(ciphertext);
+    cipher
+        .decrypt_in_place_detached(
+            Nonce::<A>::from_slice(nonce),
+            aad,
+            &mut plaintext,
+            Tag::<A>::from_slice(tag),
+        )
+        .map_err(|e| CryptoError::Aead(e.to_string()))?;
+    Ok(plaintext)
+}
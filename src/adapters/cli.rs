@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use rustyline::{
     Editor, Helper,
     completion::{Completer, Pair},
@@ -8,12 +8,30 @@ use rustyline::{
     history::DefaultHistory,
     validate::Validator,
 };
-use std::io::{self, Write};
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
 use zeroize::Zeroize;
 
 use crate::{
-    application::engine::VaultEngine,
-    domain::ports::{CryptoPort, StoragePort},
+    adapters::{
+        clipboard::ClipboardTimer,
+        password_gen::{self, GeneratorOptions},
+        recipient, recovery_key,
+    },
+    application::{engine::VaultEngine, totp},
+    domain::{
+        errors::VaultError,
+        models::{
+            CipherSuite, Credentials, CustomField, Entry, EntryFilter, EntryId, EntryIndex, EntryUpdate,
+            IdlePolicy, KdfAlgorithm, KdfParams, SecurityQuestion, SlotKind,
+        },
+        ports::{CryptoPort, StoragePort},
+    },
 };
 
 /* =======================
@@ -26,11 +44,23 @@ const YELLOW: &str = "\x1b[33m";
 const CYAN: &str = "\x1b[36m";
 const RESET: &str = "\x1b[0m";
 
+const MASK: &str = "********";
+
+// Seconds a revealed password stays on screen, unset keeps it visible
+const REVEAL_TIMEOUT_ENV: &str = "VAULT_REVEAL_TIMEOUT";
+
+// Seconds without a command before an unlocked vault is locked
+const IDLE_TIMEOUT_ENV: &str = "VAULT_IDLE_TIMEOUT";
+// "commit" or "discard" uncommitted changes on auto-lock
+const IDLE_POLICY_ENV: &str = "VAULT_IDLE_POLICY";
+
 /* =======================
    AUTOCOMPLETE
 ======================= */
 struct VaultHelper {
     commands: Vec<&'static str>,
+    tags: Vec<String>,
+    folders: Vec<String>,
 }
 
 impl Helper for VaultHelper {}
@@ -49,16 +79,26 @@ impl Completer for VaultHelper {
         pos: usize,
         _: &rustyline::Context<'_>,
     ) -> rustyline::Result<(usize, Vec<Pair>)> {
-        let start = 0;
         let input = &line[..pos];
 
-        let matches = self
-            .commands
-            .iter()
-            .filter(|cmd| cmd.starts_with(input))
-            .map(|cmd| Pair {
-                display: cmd.to_string(),
-                replacement: cmd.to_string(),
+        // Only the word under the cursor is completed
+        let start = input.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &input[start..];
+        let prev: Vec<&str> = input[..start].split_whitespace().collect();
+
+        let candidates: Vec<&str> = match prev.as_slice() {
+            [] => self.commands.clone(),
+            [.., "--tag"] => self.tags.iter().map(String::as_str).collect(),
+            ["ls" | "list", ..] => self.folders.iter().map(String::as_str).collect(),
+            _ => Vec::new(),
+        };
+
+        let matches = candidates
+            .into_iter()
+            .filter(|c| c.starts_with(word))
+            .map(|c| Pair {
+                display: c.to_string(),
+                replacement: c.to_string(),
             })
             .collect();
 
@@ -71,16 +111,165 @@ impl Completer for VaultHelper {
 ======================= */
 enum Command {
     Lock,
-    List,
+    List(EntryFilter),
     Help,
     Exit,
     Commit,
+    Passwd(Option<String>),
     Clear,
-    Get(String),
-    Unlock(String),
-    Create(String),
-    Remove(String),
-    Add { service: String, username: String },
+    Get {
+        target: Target,
+        reveal: bool,
+        copy: bool,
+    },
+    Copy(Target),
+    Edit(Target),
+    History { target: Target, show: bool },
+    Reveal(Target),
+    Search(String),
+    Totp(Target),
+    Restore(Target, usize),
+    Unlock(String, UnlockMethod),
+    Create(String, VaultOptions),
+    Rekey(VaultOptions),
+    Slots,
+    SlotAdd(VaultOptions),
+    SlotRemove(usize),
+    RecoveryRotate,
+    MemberKeygen,
+    MemberPubkey,
+    MemberAdd { public_key: String, name: String },
+    MemberRemove(usize, Option<String>),
+    Remove(Target),
+    Gen(GenSpec),
+    Add {
+        service: String,
+        username: String,
+        generate: bool,
+    },
+}
+
+// What unlock opens the vault with
+enum UnlockMethod {
+    Password(Option<String>),
+    Recovery,
+    Identity,
+}
+
+// Key derivation, cipher and key file choices for create/rekey/slot add
+struct VaultOptions {
+    kdf: KdfOverrides,
+    suite: Option<CipherSuite>,
+    keyfile: Option<String>,
+    recovery: bool,
+}
+
+// KDF flags given on the command line, applied over a base set of parameters
+#[derive(Default)]
+struct KdfOverrides {
+    algorithm: Option<KdfAlgorithm>,
+    memory_kib: Option<u32>,
+    iterations: Option<u32>,
+    parallelism: Option<u32>,
+}
+
+impl KdfOverrides {
+    fn apply(&self, base: KdfParams) -> KdfParams {
+        KdfParams {
+            algorithm: self.algorithm.unwrap_or(base.algorithm),
+            memory_kib: self.memory_kib.unwrap_or(base.memory_kib),
+            iterations: self.iterations.unwrap_or(base.iterations),
+            parallelism: self.parallelism.unwrap_or(base.parallelism),
+        }
+    }
+}
+
+impl fmt::Display for KdfOverrides {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "current KDF")?;
+        if let Some(a) = self.algorithm {
+            write!(f, " {:?}", a)?;
+        }
+        if let Some(m) = self.memory_kib {
+            write!(f, " m={}KiB", m)?;
+        }
+        if let Some(t) = self.iterations {
+            write!(f, " t={}", t)?;
+        }
+        if let Some(p) = self.parallelism {
+            write!(f, " p={}", p)?;
+        }
+        Ok(())
+    }
+}
+
+// Service with an optional username to pick one of several accounts
+struct Target {
+    service: String,
+    username: Option<String>,
+}
+
+enum GenSpec {
+    Password(GeneratorOptions),
+    Passphrase(usize),
+}
+
+/* =======================
+   IDLE WATCHDOG
+======================= */
+
+// Wakes a readline blocked at the prompt once the idle timeout passes,
+// so the vault locks without waiting for the next line. Stopped on drop
+struct IdleWatchdog {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl IdleWatchdog {
+    // rustyline's SIGINT handler makes a pending readline return
+    // ReadlineError::Interrupted; without that handler (no tty) SIGINT would kill
+    // the process, so nothing is started and idleness is checked after input
+    #[cfg(unix)]
+    fn start(after: Duration) -> Option<Self> {
+        // SAFETY: a null new action only reads the current one
+        let handled = unsafe {
+            let mut current: libc::sigaction = std::mem::zeroed();
+            libc::sigaction(libc::SIGINT, std::ptr::null(), &mut current) == 0
+                && current.sa_sigaction != libc::SIG_DFL
+                && current.sa_sigaction != libc::SIG_IGN
+        };
+        if !handled {
+            return None;
+        }
+
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            if stopped.recv_timeout(after) == Err(RecvTimeoutError::Timeout) {
+                // SAFETY: signals this process, whose handler only writes to a pipe
+                unsafe { libc::kill(libc::getpid(), libc::SIGINT) };
+            }
+        });
+
+        Some(Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn start(_: Duration) -> Option<Self> {
+        None
+    }
+}
+
+impl Drop for IdleWatchdog {
+    fn drop(&mut self) {
+        // Disconnecting wakes the thread before its timeout
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
 }
 
 /* =======================
@@ -88,6 +277,11 @@ enum Command {
 ======================= */
 pub struct VaultCli<S: StoragePort, C: CryptoPort> {
     engine: VaultEngine<S, C>,
+    clipboard: ClipboardTimer,
+    reveal_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    idle_policy: IdlePolicy,
+    last_activity: Instant,
     rl: Editor<VaultHelper, DefaultHistory>,
 }
 
@@ -95,30 +289,62 @@ pub struct VaultCli<S: StoragePort, C: CryptoPort> {
    IMPLEMENTATION
 ======================= */
 impl<S: StoragePort, C: CryptoPort> VaultCli<S, C> {
-    pub fn new(engine: VaultEngine<S, C>) -> Result<Self> {
+    pub fn new(engine: VaultEngine<S, C>, clipboard: ClipboardTimer) -> Result<Self> {
         let helper = VaultHelper {
             commands: vec![
-                "create", "unlock", "lock", "add", "get", "rm", "commit", "ls", "list", "help",
-                "exit", "clear",
+                "create", "unlock", "lock", "add", "get", "reveal", "copy", "edit", "history", "restore", "rm",
+                "commit", "passwd", "rekey", "slots", "slot", "recovery", "member", "ls", "list", "search", "totp",
+                "gen", "help", "exit", "clear",
             ],
+            tags: Vec::new(),
+            folders: Vec::new(),
         };
 
         let mut rl = Editor::<VaultHelper, DefaultHistory>::new()?;
         rl.set_helper(Some(helper));
 
-        Ok(Self { engine, rl })
+        let seconds = |var: &str| {
+            std::env::var(var)
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .map(Duration::from_secs)
+        };
+
+        let idle_policy = match std::env::var(IDLE_POLICY_ENV).as_deref() {
+            Ok("discard") => IdlePolicy::Discard,
+            _ => IdlePolicy::Commit,
+        };
+
+        Ok(Self {
+            engine,
+            clipboard,
+            reveal_timeout: seconds(REVEAL_TIMEOUT_ENV),
+            idle_timeout: seconds(IDLE_TIMEOUT_ENV),
+            idle_policy,
+            last_activity: Instant::now(),
+            rl,
+        })
     }
 
     pub fn run(&mut self) -> Result<()> {
         println!("--- Vault CLI ---\n");
 
         loop {
-            let line = match self.rl.readline(&self.prompt()) {
+            self.refresh_completions();
+            let watchdog = self.idle_watchdog();
+            let res = self.rl.readline(&self.prompt());
+            drop(watchdog);
+
+            let line = match res {
                 Ok(l) => {
                     self.rl.add_history_entry(l.as_str())?;
                     l
                 }
-                Err(ReadlineError::Interrupted) => continue,
+                // Ctrl-C, or the watchdog waking readline with SIGINT
+                Err(ReadlineError::Interrupted) => {
+                    self.lock_if_idle();
+                    continue;
+                }
                 Err(ReadlineError::Eof) => {
                     if self.confirm_exit()? {
                         break;
@@ -128,6 +354,10 @@ impl<S: StoragePort, C: CryptoPort> VaultCli<S, C> {
                 Err(e) => return Err(e.into()),
             };
 
+            if self.lock_if_idle() {
+                continue;
+            }
+
             let input = line.trim();
             if input.is_empty() {
                 continue;
@@ -151,6 +381,7 @@ impl<S: StoragePort, C: CryptoPort> VaultCli<S, C> {
             if let Err(e) = self.handle_command(cmd) {
                 eprintln!("Error: {:#}\n", e);
             }
+            self.last_activity = Instant::now();
         }
 
         Ok(())
@@ -164,16 +395,115 @@ impl<S: StoragePort, C: CryptoPort> VaultCli<S, C> {
         let cmd = p.next()?;
 
         Some(match cmd {
-            "unlock" => Command::Unlock(p.next()?.into()),
-            "create" => Command::Create(p.next()?.into()),
+            "unlock" => {
+                let vault = p.next()?.into();
+                let method = match p.next() {
+                    Some("--recovery") => UnlockMethod::Recovery,
+                    Some("--identity") => UnlockMethod::Identity,
+                    Some("--keyfile") => UnlockMethod::Password(Some(p.next()?.into())),
+                    None => UnlockMethod::Password(None),
+                    Some(_) => return None,
+                };
+                if p.next().is_some() {
+                    return None;
+                }
+                Command::Unlock(vault, method)
+            }
+            "create" => Command::Create(p.next()?.into(), Self::parse_vault_opts(p)?),
+            "rekey" => {
+                let opts = Self::parse_vault_opts(p)?;
+                if opts.recovery {
+                    return None;
+                }
+                Command::Rekey(opts)
+            }
+            "recovery" => match p.next()? {
+                "rotate" => Command::RecoveryRotate,
+                _ => return None,
+            },
+            "member" => match p.next()? {
+                "keygen" => Command::MemberKeygen,
+                "pubkey" => Command::MemberPubkey,
+                "add" => Command::MemberAdd {
+                    public_key: p.next()?.into(),
+                    name: p.next()?.into(),
+                },
+                "rm" => {
+                    let n = p.next()?.parse().ok()?;
+                    Command::MemberRemove(n, Self::parse_keyfile(p)?)
+                }
+                _ => return None,
+            },
+            "slots" => Command::Slots,
+            "slot" => match p.next()? {
+                "add" => {
+                    let opts = Self::parse_vault_opts(p)?;
+                    if opts.suite.is_some() || opts.recovery {
+                        return None;
+                    }
+                    Command::SlotAdd(opts)
+                }
+                "rm" => Command::SlotRemove(p.next()?.parse().ok()?),
+                _ => return None,
+            },
             "add" => Command::Add {
                 service: p.next()?.into(),
                 username: p.next()?.into(),
+                generate: match p.next() {
+                    None => false,
+                    Some("--generate" | "-g") => true,
+                    Some(_) => return None,
+                },
             },
-            "get" => Command::Get(p.next()?.into()),
-            "rm" => Command::Remove(p.next()?.into()),
+            "gen" => Command::Gen(Self::parse_gen(p)?),
+            "get" => {
+                let (mut args, flags) = Self::split_flags(p);
+                Command::Get {
+                    target: Self::parse_target(&mut args)?,
+                    reveal: flags.contains(&"--show") || flags.contains(&"--reveal"),
+                    copy: flags.contains(&"--copy"),
+                }
+            }
+            "copy" => Command::Copy(Self::parse_target(&mut p)?),
+            "edit" => Command::Edit(Self::parse_target(&mut p)?),
+            "history" => {
+                let (mut args, flags) = Self::split_flags(p);
+                Command::History {
+                    target: Self::parse_target(&mut args)?,
+                    show: flags.contains(&"--show"),
+                }
+            }
+            "reveal" => Command::Reveal(Self::parse_target(&mut p)?),
+            "totp" => Command::Totp(Self::parse_target(&mut p)?),
+            "search" => {
+                let query: Vec<&str> = p.collect();
+                if query.is_empty() {
+                    return None;
+                }
+                Command::Search(query.join(" "))
+            }
+            "restore" => {
+                let service = p.next()?.into();
+                let n = p.next()?.parse().ok()?;
+                let target = Target {
+                    service,
+                    username: p.next().map(Into::into),
+                };
+                Command::Restore(target, n)
+            }
+            "rm" => Command::Remove(Self::parse_target(&mut p)?),
             "commit" => Command::Commit,
-            "ls" | "list" => Command::List,
+            "passwd" => Command::Passwd(Self::parse_keyfile(p)?),
+            "ls" | "list" => {
+                let mut filter = EntryFilter::default();
+                while let Some(arg) = p.next() {
+                    match arg {
+                        "--tag" => filter.tag = Some(p.next()?.into()),
+                        folder => filter.folder = Some(folder.into()),
+                    }
+                }
+                Command::List(filter)
+            }
             "lock" => Command::Lock,
             "help" => Command::Help,
             "clear" => Command::Clear,
@@ -182,30 +512,500 @@ impl<S: StoragePort, C: CryptoPort> VaultCli<S, C> {
         })
     }
 
+    fn split_flags<'a>(
+        args: impl Iterator<Item = &'a str>,
+    ) -> (std::vec::IntoIter<&'a str>, Vec<&'a str>) {
+        let (flags, positional): (Vec<&str>, Vec<&str>) = args.partition(|a| a.starts_with("--"));
+        (positional.into_iter(), flags)
+    }
+
+    // --kdf <alg> --memory <KiB> --iterations <n> --parallelism <n>,
+    // --cipher <suite>, --keyfile <path> and --recovery
+    fn parse_vault_opts<'a>(mut args: impl Iterator<Item = &'a str>) -> Option<VaultOptions> {
+        let mut kdf = KdfOverrides::default();
+        let mut suite = None;
+        let mut keyfile = None;
+        let mut recovery = false;
+
+        while let Some(arg) = args.next() {
+            match arg {
+                "--kdf" => {
+                    kdf.algorithm = Some(match args.next()? {
+                        "argon2d" => KdfAlgorithm::Argon2d,
+                        "argon2i" => KdfAlgorithm::Argon2i,
+                        "argon2id" => KdfAlgorithm::Argon2id,
+                        _ => return None,
+                    })
+                }
+                "--memory" => kdf.memory_kib = Some(args.next()?.parse().ok()?),
+                "--iterations" => kdf.iterations = Some(args.next()?.parse().ok()?),
+                "--parallelism" => kdf.parallelism = Some(args.next()?.parse().ok()?),
+                "--cipher" => {
+                    suite = Some(match args.next()? {
+                        "aes256gcm" => CipherSuite::Aes256Gcm,
+                        "xchacha20poly1305" => CipherSuite::XChaCha20Poly1305,
+                        _ => return None,
+                    })
+                }
+                "--keyfile" => keyfile = Some(args.next()?.into()),
+                "--recovery" => recovery = true,
+                _ => return None,
+            }
+        }
+
+        Some(VaultOptions {
+            kdf,
+            suite,
+            keyfile,
+            recovery,
+        })
+    }
+
+    // Optional --keyfile <path> and nothing else
+    fn parse_keyfile<'a>(mut args: impl Iterator<Item = &'a str>) -> Option<Option<String>> {
+        match args.next() {
+            None => Some(None),
+            Some("--keyfile") => {
+                let path = args.next()?;
+                args.next().is_none().then(|| Some(path.into()))
+            }
+            Some(_) => None,
+        }
+    }
+
+    fn parse_target<'a>(args: &mut impl Iterator<Item = &'a str>) -> Option<Target> {
+        Some(Target {
+            service: args.next()?.into(),
+            username: args.next().map(Into::into),
+        })
+    }
+
+    fn parse_gen<'a>(mut args: impl Iterator<Item = &'a str>) -> Option<GenSpec> {
+        let mut opts = GeneratorOptions::default();
+        let mut words = None;
+
+        while let Some(arg) = args.next() {
+            match arg {
+                "--words" | "-w" => words = Some(args.next()?.parse().ok()?),
+                "--no-lower" => opts.lower = false,
+                "--no-upper" => opts.upper = false,
+                "--no-digits" => opts.digits = false,
+                "--no-symbols" => opts.symbols = false,
+                "--no-ambiguous" => opts.exclude_ambiguous = true,
+                n => opts.length = n.parse().ok()?,
+            }
+        }
+
+        Some(match words {
+            Some(w) => GenSpec::Passphrase(w),
+            None => GenSpec::Password(opts),
+        })
+    }
+
     /* =======================
        COMMAND HANDLER
     ======================= */
     fn handle_command(&mut self, cmd: Command) -> Result<()> {
         match cmd {
-            Command::Unlock(v) => {
+            Command::Unlock(v, UnlockMethod::Password(keyfile)) => {
+                let mut key = Self::read_keyfile(keyfile.as_deref())?;
                 let mut pw = self.request_password("Vault password: ");
-                self.engine.unlock(&v, &pw)?;
+                let res = self.engine.unlock(&v, &Credentials::new(&pw, key.as_deref()));
                 pw.zeroize();
-                println!("Vault '{}' unlocked.\n", v);
+                key.zeroize();
+                res?;
+                println!("Vault '{}' unlocked ({}).", v, self.vault_path());
+                if let Some(version) = self.engine.migrated_from() {
+                    println!("Upgraded from format v{}, commit to save it.", version);
+                }
+                println!();
             }
 
-            Command::Create(v) => {
+            Command::Unlock(v, UnlockMethod::Recovery) => {
+                let mut input = self.request_password("Recovery key: ");
+                let phrase = recovery_key::normalize(&input);
+                input.zeroize();
+
+                let mut phrase = phrase?;
+                let res = self.engine.unlock_recovery(&v, &Credentials::new(&phrase, None));
+                phrase.zeroize();
+                res?;
+                println!("Vault '{}' unlocked with its recovery key ({}).", v, self.vault_path());
+                println!("Add a new password with 'slot add', then 'slot rm' the lost one.\n");
+            }
+
+            Command::Unlock(v, UnlockMethod::Identity) => {
+                let mut secret = recipient::load_identity()?;
+                let res = self.engine.unlock_identity(&v, &secret);
+                secret.zeroize();
+                res?;
+                println!("Vault '{}' unlocked as a member ({}).\n", v, self.vault_path());
+            }
+
+            Command::Create(v, opts) => {
+                let suite = opts.suite.unwrap_or_default();
+                let mut key = Self::read_keyfile(opts.keyfile.as_deref())?;
                 let mut pw = self.request_password("New vault password: ");
-                self.engine.create_vault(&v, &pw)?;
+                let mut phrase = opts.recovery.then(recovery_key::generate);
+
+                let creds = Credentials::new(&pw, key.as_deref());
+                let recovery = phrase.as_deref().map(|p| Credentials::new(p, None));
+                let kdf = opts.kdf.apply(KdfParams::default());
+                let res = self.engine.create_vault(&v, &creds, kdf, suite, recovery.as_ref());
+                pw.zeroize();
+                key.zeroize();
+                if let Err(e) = res {
+                    phrase.zeroize();
+                    return Err(e.into());
+                }
+
+                println!("Vault '{}' created ({}).\n", v, self.vault_path());
+                if let Some(p) = &phrase {
+                    Self::print_recovery_key(p);
+                }
+                phrase.zeroize();
+            }
+
+            Command::Rekey(opts) => {
+                let current_suite = self.engine.suite()?;
+                let suite = opts.suite.unwrap_or(current_suite);
+                println!("Current: {}", current_suite);
+                println!("New:     {}, {}", suite, opts.kdf);
+                // A new cipher means a new data key: members are wrapped again, but only
+                // the password slot unlocked with survives and the recovery key changes
+                let suite_changed = suite != current_suite;
+                let slots = self.engine.key_slots()?;
+                let passwords = slots.iter().filter(|s| s.kind == SlotKind::Password).count();
+                let has_recovery = slots.iter().any(|s| s.kind == SlotKind::Recovery);
+                if suite_changed && passwords > 1 {
+                    println!("A new cipher drops the other {} password slots.", passwords - 1);
+                }
+                if suite_changed && has_recovery {
+                    println!("The recovery key will be replaced by a new one.");
+                }
+                if !self.confirm("Re-derive the key and commit?") {
+                    println!("Aborted.\n");
+                    return Ok(());
+                }
+
+                let mut phrase = (suite_changed && has_recovery).then(recovery_key::generate);
+
+                let mut key = Self::read_keyfile(opts.keyfile.as_deref())?;
+                let mut pw = self.request_password("Vault password: ");
+                let creds = Credentials::new(&pw, key.as_deref());
+                let recovery = phrase.as_deref().map(|p| Credentials::new(p, None));
+                // Flags not given keep the values of the slot the password opens
+                let res = self.engine.rekey(
+                    &creds,
+                    |current| opts.kdf.apply(current),
+                    suite,
+                    recovery.as_ref(),
+                );
+                pw.zeroize();
+                key.zeroize();
+                if let Err(e) = res {
+                    phrase.zeroize();
+                    return Err(e.into());
+                }
+
+                println!("Vault re-keyed.");
+                if let Some(p) = &phrase {
+                    println!("The old recovery key no longer works, this one replaces it:");
+                    Self::print_recovery_key(p);
+                } else {
+                    println!();
+                }
+                phrase.zeroize();
+            }
+
+            Command::Slots => {
+                for (i, slot) in self.engine.key_slots()?.iter().enumerate() {
+                    if let Some(member) = &slot.member {
+                        let key = recipient::encode_key(&member.public_key);
+                        let short = &key[..16];
+                        println!("{}  {:<8}  {} ({}…)", i + 1, slot.kind, member.name, short);
+                        continue;
+                    }
+                    let keyfile = if slot.keyfile { ", key file" } else { "" };
+                    println!("{}  {:<8}  {}{}", i + 1, slot.kind, slot.kdf, keyfile);
+                }
+                println!();
+            }
+
+            Command::RecoveryRotate => {
+                if self.engine.is_locked() {
+                    return Err(VaultError::Locked.into());
+                }
+                if self.engine.is_dirty()
+                    && !self.confirm("Uncommitted changes will be committed too. Continue?")
+                {
+                    println!("Aborted.\n");
+                    return Ok(());
+                }
+                if !self.confirm("Replace the recovery key? The current one stops working.") {
+                    println!("Aborted.\n");
+                    return Ok(());
+                }
+
+                let mut phrase = recovery_key::generate();
+                let res = self.engine.rotate_recovery(&Credentials::new(&phrase, None));
+                if res.is_ok() {
+                    Self::print_recovery_key(&phrase);
+                }
+                phrase.zeroize();
+                res?;
+            }
+
+            Command::MemberKeygen => {
+                let public = recipient::create_identity()?;
+                println!("Identity written to {}.", recipient::identity_path().display());
+                println!("Public key: {}\n", recipient::encode_key(&public));
+            }
+
+            Command::MemberPubkey => {
+                let mut secret = recipient::load_identity()?;
+                let public = recipient::public_key(&secret);
+                secret.zeroize();
+                println!("{}\n", recipient::encode_key(&public));
+            }
+
+            Command::MemberAdd { public_key, name } => {
+                let key = recipient::decode_key(&public_key)
+                    .ok_or(anyhow!("Public key must be 64 hex characters"))?;
+                let n = self.engine.add_member(&name, key)?;
+                println!("Member slot {} added for '{}', commit to save it.\n", n, name);
+            }
+
+            Command::MemberRemove(n, keyfile) => {
+                if self.engine.is_locked() {
+                    return Err(VaultError::Locked.into());
+                }
+                if !self.confirm(&format!("Remove member slot {} and re-key the vault?", n)) {
+                    println!("Aborted.\n");
+                    return Ok(());
+                }
+
+                // Only the password slot reopened here survives the new data key
+                let slots = self.engine.key_slots()?;
+                let passwords = slots.iter().filter(|s| s.kind == SlotKind::Password).count();
+                let has_recovery = slots.iter().any(|s| s.kind == SlotKind::Recovery);
+                if passwords > 1 {
+                    println!("The other {} password slots will be dropped.", passwords - 1);
+                }
+                if has_recovery {
+                    println!("The recovery key will be replaced by a new one.");
+                }
+                let mut key = Self::read_keyfile(keyfile.as_deref())?;
+                let mut pw = if passwords > 0 {
+                    self.request_password("Vault password (empty drops all password slots): ")
+                } else {
+                    String::new()
+                };
+                if passwords > 0 && pw.is_empty() && !self.confirm("Drop every password slot?") {
+                    key.zeroize();
+                    println!("Aborted.\n");
+                    return Ok(());
+                }
+
+                let mut phrase = has_recovery.then(recovery_key::generate);
+                let creds = Credentials::new(&pw, key.as_deref());
+                let recovery = phrase.as_deref().map(|p| Credentials::new(p, None));
+                let keep = (!pw.is_empty()).then_some(&creds);
+                let res = self.engine.remove_member(n, keep, recovery.as_ref());
+                pw.zeroize();
+                key.zeroize();
+                if let Err(e) = res {
+                    phrase.zeroize();
+                    return Err(e.into());
+                }
+
+                println!("Member slot {} removed and vault re-keyed, commit to save it.", n);
+                if let Some(p) = &phrase {
+                    println!("After the commit only this recovery key works:");
+                    Self::print_recovery_key(p);
+                } else {
+                    println!();
+                }
+                phrase.zeroize();
+            }
+
+            Command::SlotAdd(opts) => {
+                // Unless overridden, match the costs of the vault's password slot
+                let base = self
+                    .engine
+                    .key_slots()?
+                    .iter()
+                    .find(|s| s.kind == SlotKind::Password)
+                    .map_or_else(KdfParams::default, |s| s.kdf);
+                let kdf = opts.kdf.apply(base);
+
+                let mut key = Self::read_keyfile(opts.keyfile.as_deref())?;
+                let mut pw = self.request_password("Slot password: ");
+                let mut repeat = self.request_password("Repeat slot password: ");
+
+                let res = if pw != repeat {
+                    Err(anyhow!("Passwords do not match"))
+                } else {
+                    let creds = Credentials::new(&pw, key.as_deref());
+                    self.engine.add_slot(&creds, kdf).map_err(Into::into)
+                };
+                pw.zeroize();
+                repeat.zeroize();
+                key.zeroize();
+
+                println!("Key slot {} added, commit to save it.\n", res?);
+            }
+
+            Command::SlotRemove(n) => {
+                if self.confirm(&format!("Remove key slot {}?", n)) {
+                    self.engine.remove_slot(n)?;
+                    println!("Key slot {} removed, commit to save it.\n", n);
+                } else {
+                    println!("Aborted.\n");
+                }
+            }
+
+            Command::Add {
+                service,
+                username,
+                generate,
+            } => {
+                let mut pw = if generate {
+                    password_gen::generate_password(&GeneratorOptions::default())?
+                } else {
+                    self.request_password("Service password: ")
+                };
+                let res = self.engine.add(&service, &username, &pw);
                 pw.zeroize();
-                println!("Vault '{}' created.\n", v);
+                let id = res?;
+
+                if self.confirm("Add URLs, notes or custom fields?") {
+                    let mut update = EntryUpdate::default();
+                    let mut entry = self.engine.get(id)?;
+                    let details = self.collect_details(&entry, &mut update);
+                    entry.zeroize();
+                    details?;
+                    let res = self.engine.update(id, &update);
+                    update.zeroize();
+                    res?;
+                }
+                println!("Entry '{} ({})' added.\n", service, username);
             }
 
-            Command::Add { service, username } => {
-                let mut pw = self.request_password("Service password: ");
-                self.engine.add(&service, &username, &pw)?;
+            Command::Gen(spec) => {
+                let mut pw = match spec {
+                    GenSpec::Password(opts) => password_gen::generate_password(&opts)?,
+                    GenSpec::Passphrase(words) => password_gen::generate_passphrase(words, "-")?,
+                };
+                println!("{}\n", pw);
                 pw.zeroize();
-                println!("Entry '{}' added.\n", service);
+            }
+
+            Command::Edit(t) => {
+                let id = self.select(&t)?;
+                let (service, username) = {
+                    let e = self.engine.get_index(id)?;
+                    (e.service.clone(), e.username.clone())
+                };
+                let mut update = EntryUpdate {
+                    service: self.read_field("Service", &service),
+                    username: self.read_field("Username", &username),
+                    ..Default::default()
+                };
+                if self.confirm("Change password?") {
+                    update.passwd = Some(self.request_password("New service password: "));
+                }
+                let mut entry = self.engine.get(id)?;
+                let details = self.collect_details(&entry, &mut update);
+                entry.zeroize();
+                if let Err(e) = details {
+                    update.zeroize();
+                    return Err(e);
+                }
+
+                let changed = !update.is_empty();
+                let res = self.engine.update(id, &update);
+                update.zeroize();
+                res?;
+
+                if !changed {
+                    println!("Nothing changed.\n");
+                } else {
+                    println!("Entry '{}' updated.\n", Self::label(self.engine.get_index(id)?));
+                }
+            }
+
+            Command::History { target, show } => {
+                let id = self.select(&target)?;
+                let mut history = self.engine.history(id)?;
+                if history.is_empty() {
+                    println!("No previous passwords for '{}'.", target.service);
+                }
+                let lines = |show: bool| -> Vec<String> {
+                    let mut lines = Vec::new();
+                    for (i, record) in history.iter().enumerate() {
+                        let when = chrono::DateTime::from_timestamp(record.replaced_at, 0)
+                            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                            .unwrap_or_default();
+                        let passwd = if show { record.passwd.as_str() } else { MASK };
+                        lines.push(format!("  {}) {}  {}", i + 1, when, passwd));
+                    }
+                    lines
+                };
+
+                let masked = lines(false);
+                let res = if show {
+                    Self::reveal_lines(lines(true), &masked, self.reveal_timeout)
+                } else {
+                    masked.iter().for_each(|line| println!("{}", line));
+                    Ok(())
+                };
+                history.zeroize();
+                println!();
+                res?;
+            }
+
+            Command::Restore(t, n) => {
+                let id = self.select(&t)?;
+                let label = Self::label(self.engine.get_index(id)?);
+                if self.confirm(&format!("Restore password {} of '{}'?", n, label)) {
+                    self.engine.restore(id, n)?;
+                    println!("Password of '{}' restored.\n", label);
+                } else {
+                    println!("Aborted.\n");
+                }
+            }
+
+            Command::Search(query) => {
+                let hits = self.engine.search(&query)?;
+                if hits.is_empty() {
+                    println!("No matches for '{}'.\n", query);
+                    return Ok(());
+                }
+
+                for (i, e) in hits.iter().enumerate() {
+                    println!("  {}) {}", i + 1, Self::label(e));
+                }
+                let choice = self.read_line(&format!("Open entry [1-{}, empty to cancel]: ", hits.len()));
+                println!();
+
+                if let Some(e) = choice
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| n.checked_sub(1))
+                    .and_then(|n| hits.get(n))
+                {
+                    let mut entry = self.engine.get(e.id)?;
+                    let res = Self::print_entry(&entry, false, None);
+                    entry.zeroize();
+                    res?;
+                }
+            }
+
+            Command::Totp(t) => {
+                let id = self.select(&t)?;
+                let (code, remaining) = self.engine.totp(id)?;
+                println!("{}  ({}s remaining)\n", code, remaining);
             }
 
             Command::Commit => {
@@ -213,37 +1013,105 @@ impl<S: StoragePort, C: CryptoPort> VaultCli<S, C> {
                 println!("Changes committed.\n");
             }
 
-            Command::Remove(s) => {
-                if self.confirm(&format!("Remove '{}'?", s)) {
-                    self.engine.delete(&s)?;
-                    println!("Entry '{}' removed.\n", s);
+            Command::Passwd(keyfile) => {
+                if self.engine.is_locked() {
+                    return Err(VaultError::Locked.into());
+                }
+                if self.engine.is_dirty()
+                    && !self.confirm("Uncommitted changes will be committed too. Continue?")
+                {
+                    println!("Aborted.\n");
+                    return Ok(());
+                }
+
+                // The key file, if any, stays the same across the change
+                let mut key = Self::read_keyfile(keyfile.as_deref())?;
+                let mut old = self.request_password("Current vault password: ");
+                let mut new = self.request_password("New vault password: ");
+                let mut repeat = self.request_password("Repeat new password: ");
+
+                let res = if new != repeat {
+                    Err(anyhow!("Passwords do not match"))
+                } else {
+                    self.engine
+                        .change_password(
+                            &Credentials::new(&old, key.as_deref()),
+                            &Credentials::new(&new, key.as_deref()),
+                        )
+                        .map_err(Into::into)
+                };
+                old.zeroize();
+                new.zeroize();
+                repeat.zeroize();
+                key.zeroize();
+                res?;
+
+                println!("Vault password changed.\n");
+            }
+
+            Command::Remove(t) => {
+                let id = self.select(&t)?;
+                let label = Self::label(self.engine.get_index(id)?);
+                if self.confirm(&format!("Remove '{}'?", label)) {
+                    self.engine.delete(id)?;
+                    println!("Entry '{}' removed.\n", label);
                 } else {
                     println!("Aborted.\n");
                 }
             }
 
-            Command::Get(s) => {
-                let e = self.engine.get(&s)?;
-                println!(
-                    "{}\n  user: {}\n  pass: {}\n",
-                    e.service, e.username, e.passwd
-                );
+            Command::Get {
+                target,
+                reveal,
+                copy,
+            } => {
+                let id = self.select(&target)?;
+                let mut entry = self.engine.get(id)?;
+                let res = Self::print_entry(&entry, reveal, self.reveal_timeout);
+                entry.zeroize();
+                res?;
+                if copy {
+                    self.copy_password(id)?;
+                }
+            }
+
+            Command::Reveal(t) => {
+                let id = self.select(&t)?;
+                println!("{}", Self::label(self.engine.get_index(id)?));
+                let mut e = self.engine.get(id)?;
+                let res = Self::reveal_line("pass", &e.passwd, self.reveal_timeout);
+                e.zeroize();
+                res?;
+                println!();
             }
 
-            Command::List => {
+            Command::Copy(t) => {
+                let id = self.select(&t)?;
+                self.copy_password(id)?;
+            }
+
+            Command::List(filter) => {
                 if self.engine.is_locked() {
                     for v in self.engine.get_vaults()? {
                         println!("  {}", v);
                     }
                 } else {
-                    for e in self.engine.get_entries()? {
-                        println!("  {}", e);
+                    for e in self.engine.filter_entries(&filter)? {
+                        let folder = e.folder.as_deref().map(|f| format!("  [{}]", f));
+                        let tags: String = e.tags.iter().map(|t| format!(" #{}", t)).collect();
+                        println!("  {}{}{}", Self::label(e), folder.unwrap_or_default(), tags);
                     }
                 }
                 println!();
             }
 
             Command::Lock => {
+                if self.engine.is_dirty()
+                    && !self.confirm("You have uncommitted changes. Lock and discard them?")
+                {
+                    println!("Aborted.\n");
+                    return Ok(());
+                }
                 self.engine.lock()?;
                 println!("Vault locked.\n");
             }
@@ -292,6 +1160,47 @@ impl<S: StoragePort, C: CryptoPort> VaultCli<S, C> {
         }
     }
 
+    /* =======================
+       AUTO-LOCK
+    ======================= */
+
+    // Only while unlocked, for whatever is left of the timeout
+    fn idle_watchdog(&self) -> Option<IdleWatchdog> {
+        if self.engine.is_locked() {
+            return None;
+        }
+        let remaining = self.idle_timeout?.saturating_sub(self.last_activity.elapsed());
+        IdleWatchdog::start(remaining)
+    }
+
+    // Called when the watchdog wakes readline, and once input arrives for
+    // terminals it cannot wake; input typed into an expired session is discarded
+    // Never fails: an idle session is locked even if committing its changes does not work
+    fn lock_if_idle(&mut self) -> bool {
+        let expired = self
+            .idle_timeout
+            .is_some_and(|t| self.last_activity.elapsed() >= t);
+        self.last_activity = Instant::now();
+
+        if !expired || self.engine.is_locked() {
+            return false;
+        }
+
+        let committed = self.engine.is_dirty() && self.idle_policy == IdlePolicy::Commit;
+        let res = self.engine.auto_lock(self.idle_policy);
+        self.clipboard.clear().ok();
+
+        match res {
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                println!("Vault auto-locked after inactivity, uncommitted changes were lost.\n");
+            }
+            Ok(()) if committed => println!("Vault auto-locked after inactivity, changes committed.\n"),
+            Ok(()) => println!("Vault auto-locked after inactivity.\n"),
+        }
+        true
+    }
+
     /* =======================
        PROMPT
     ======================= */
@@ -322,6 +1231,274 @@ impl<S: StoragePort, C: CryptoPort> VaultCli<S, C> {
         rpassword::read_password().unwrap()
     }
 
+    fn print_recovery_key(phrase: &str) {
+        println!("Recovery key, keep it offline. It is not shown again:\n");
+        let words: Vec<&str> = phrase.split(' ').collect();
+        for row in words.chunks(6) {
+            println!("    {}", row.join(" "));
+        }
+        println!();
+    }
+
+    fn read_keyfile(path: Option<&str>) -> Result<Option<Vec<u8>>> {
+        path.map(|p| fs::read(p).map_err(|e| anyhow!("Cannot read key file '{}': {}", p, e)))
+            .transpose()
+    }
+
+    fn vault_path(&self) -> String {
+        self.engine
+            .current_path()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default()
+    }
+
+    // Asks which account is meant when a service has several
+    fn select(&self, target: &Target) -> Result<EntryId> {
+        let accounts = self
+            .engine
+            .find(&target.service, target.username.as_deref())?;
+        if let [entry] = accounts.as_slice() {
+            return Ok(entry.id);
+        }
+
+        println!("Several accounts for '{}':", target.service);
+        for (i, e) in accounts.iter().enumerate() {
+            println!("  {}) {}", i + 1, e.username);
+        }
+        print!("Choose an account [1-{}]: ", accounts.len());
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        input
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|n| (1..=accounts.len()).contains(n))
+            .map(|n| accounts[n - 1].id)
+            .ok_or(anyhow!("Invalid selection"))
+    }
+
+    // Password, security answers and secret fields stay masked unless revealed
+    // Revealed secrets are masked again after `timeout`, like `reveal`
+    fn print_entry(e: &Entry, reveal: bool, timeout: Option<Duration>) -> Result<()> {
+        let masked = Self::entry_lines(e, false);
+        let res = if reveal {
+            Self::reveal_lines(Self::entry_lines(e, true), &masked, timeout)
+        } else {
+            masked.iter().for_each(|line| println!("{}", line));
+            Ok(())
+        };
+        println!();
+        res
+    }
+
+    fn entry_lines(e: &Entry, reveal: bool) -> Vec<String> {
+        let secret = |value: &str, is_secret: bool| {
+            if is_secret && !reveal {
+                MASK.to_string()
+            } else {
+                value.to_string()
+            }
+        };
+
+        let mut lines = vec![e.service.clone(), format!("  user: {}", e.username)];
+        lines.push(format!("  pass: {}", secret(&e.passwd, true)));
+        if e.totp.is_some() {
+            lines.push("  totp: configured".into());
+        }
+        for url in &e.urls {
+            lines.push(format!("  url:  {}", url));
+        }
+        for q in &e.questions {
+            lines.push(format!("  q:    {} -> {}", q.question, secret(&q.answer, true)));
+        }
+        for f in &e.fields {
+            lines.push(format!("  {}: {}", f.name, secret(&f.value, f.secret)));
+        }
+        if let Some(notes) = &e.notes {
+            lines.push("  notes:".into());
+            for line in notes.lines() {
+                lines.push(format!("    {}", line));
+            }
+        }
+        lines
+    }
+
+    // Prompts for URLs, notes, tags, folder, security questions and custom fields
+    fn collect_details(&self, e: &Entry, update: &mut EntryUpdate) -> Result<()> {
+        let urls = e.urls.join(", ");
+        update.urls = match self.read_field("URLs (comma separated, - to clear)", &urls) {
+            Some(v) if v == "-" => Some(Vec::new()),
+            Some(v) => Some(Self::split_list(&v)),
+            None => None,
+        };
+
+        let notes = e.notes.as_deref().unwrap_or("");
+        update.notes = match self.read_field("Notes (- to clear)", notes) {
+            Some(v) if v == "-" => Some(None),
+            Some(v) => Some(Some(v)),
+            None => None,
+        };
+
+        let tags = e.tags.join(", ");
+        update.tags = match self.read_field("Tags (comma separated, - to clear)", &tags) {
+            Some(v) if v == "-" => Some(Vec::new()),
+            Some(v) => Some(Self::split_list(&v)),
+            None => None,
+        };
+
+        let folder = e.folder.as_deref().unwrap_or("");
+        update.folder = match self.read_field("Folder (e.g. work/dev, - to clear)", folder) {
+            Some(v) if v == "-" => Some(None),
+            Some(v) => Some(Some(v)),
+            None => None,
+        };
+
+        let totp_state = if e.totp.is_some() { "set" } else { "none" };
+        if self.confirm(&format!("Change TOTP secret ({})?", totp_state)) {
+            let mut input = self.request_password("Base32 secret or otpauth:// URI (- to clear): ");
+            let parsed = match input.trim() {
+                "-" => Ok(None),
+                _ => totp::parse(&input).map(Some),
+            };
+            input.zeroize();
+            update.totp = Some(parsed?);
+        }
+
+        if self.confirm(&format!("Replace security questions ({})?", e.questions.len())) {
+            let mut questions = Vec::new();
+            loop {
+                let question = self.read_line("Question (empty to finish): ");
+                if question.is_empty() {
+                    break;
+                }
+                let answer = self.request_password("Answer: ");
+                questions.push(SecurityQuestion { question, answer });
+            }
+            update.questions = Some(questions);
+        }
+
+        if self.confirm(&format!("Replace custom fields ({})?", e.fields.len())) {
+            let mut fields = Vec::new();
+            loop {
+                let name = self.read_line("Field name (empty to finish): ");
+                if name.is_empty() {
+                    break;
+                }
+                let secret = self.confirm("Secret?");
+                let value = if secret {
+                    self.request_password("Value: ")
+                } else {
+                    self.read_line("Value: ")
+                };
+                fields.push(CustomField {
+                    name,
+                    value,
+                    secret,
+                });
+            }
+            update.fields = Some(fields);
+        }
+
+        Ok(())
+    }
+
+    // Prints a secret, then overwrites the line with a mask after `timeout`
+    fn reveal_line(name: &str, value: &str, timeout: Option<Duration>) -> Result<()> {
+        let Some(timeout) = timeout else {
+            println!("  {}: {}", name, value);
+            return Ok(());
+        };
+
+        print!("  {}: {}", name, value);
+        io::stdout().flush()?;
+        thread::sleep(timeout);
+        println!("\r\x1b[2K  {}: {}", name, MASK);
+        Ok(())
+    }
+
+    // Prints lines holding secrets, then redraws them as `masked` after `timeout`.
+    // Assumes none of the lines wrapped
+    fn reveal_lines(mut shown: Vec<String>, masked: &[String], timeout: Option<Duration>) -> Result<()> {
+        shown.iter().for_each(|line| println!("{}", line));
+        shown.zeroize();
+
+        let Some(timeout) = timeout.filter(|_| !masked.is_empty()) else {
+            return Ok(());
+        };
+        io::stdout().flush()?;
+        thread::sleep(timeout);
+
+        print!("\x1b[{}A", masked.len());
+        for line in masked {
+            println!("\r\x1b[2K{}", line);
+        }
+        Ok(())
+    }
+
+    fn copy_password(&mut self, id: EntryId) -> Result<()> {
+        let label = Self::label(self.engine.get_index(id)?);
+        let mut e = self.engine.get(id)?;
+        let res = self.clipboard.copy(&e.passwd);
+        e.zeroize();
+        res?;
+        println!(
+            "Password of '{}' copied, clearing in {}s.\n",
+            label,
+            self.clipboard.timeout().as_secs()
+        );
+        Ok(())
+    }
+
+    fn split_list(value: &str) -> Vec<String> {
+        value
+            .split(',')
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .collect()
+    }
+
+    // Feeds current tags and folders to tab completion
+    fn refresh_completions(&mut self) {
+        let (tags, folders) = if self.engine.is_locked() {
+            (Vec::new(), Vec::new())
+        } else {
+            (
+                self.engine.tags().into_iter().collect(),
+                self.engine.folders().into_iter().collect(),
+            )
+        };
+
+        if let Some(helper) = self.rl.helper_mut() {
+            helper.tags = tags;
+            helper.folders = folders;
+        }
+    }
+
+    fn label(entry: &EntryIndex) -> String {
+        format!("{} ({})", entry.service, entry.username)
+    }
+
+    fn read_line(&self, label: &str) -> String {
+        print!("{}", label);
+        io::stdout().flush().unwrap();
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).ok();
+        input.trim().to_string()
+    }
+
+    // Empty input keeps the current value
+    fn read_field(&self, label: &str, current: &str) -> Option<String> {
+        print!("{} [{}]: ", label, current);
+        io::stdout().flush().unwrap();
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).ok();
+        let value = input.trim();
+        (!value.is_empty() && value != current).then(|| value.to_string())
+    }
+
     fn confirm(&self, msg: &str) -> bool {
         print!("{} (y/N): ", msg);
         io::stdout().flush().unwrap();
@@ -333,14 +1510,49 @@ impl<S: StoragePort, C: CryptoPort> VaultCli<S, C> {
     fn print_help() {
         println!(
             r#"
-create <name>        Create vault
-unlock <name>        Unlock vault
+create <name> [opts] Create vault (--kdf argon2id|argon2i|argon2d,
+                     --memory <KiB>, --iterations <n>, --parallelism <n>,
+                     --cipher aes256gcm|xchacha20poly1305,
+                     --keyfile <path> to also require a key file,
+                     --recovery to print a recovery key)
+unlock <name> [--keyfile <path> | --recovery | --identity]
+                     Unlock vault, --recovery asks for the recovery key,
+                     --identity uses the member identity file
 lock                 Lock vault
-add <svc> <user>     Add entry
-get <svc>            Get entry
-rm <svc>             Remove entry
+add <svc> <user>     Add entry (--generate for a random password),
+                     optionally with URLs, notes and custom fields
+get <svc> [user]     Get entry, secrets masked (--show reveals them
+                     like reveal, --copy copies the password)
+reveal <svc> [user]  Show password, hidden again after
+                     $VAULT_REVEAL_TIMEOUT seconds if set
+copy <svc> [user]    Copy password to clipboard
+edit <svc> [user]    Edit entry
+history <svc> [user] Show previous passwords (--show reveals them)
+search <query>       Fuzzy search entries
+totp <svc> [user]    Show current TOTP code
+restore <svc> <n> [user]
+                     Restore previous password n
+rm <svc> [user]      Remove entry
 commit               Save changes
-ls                   List vaults or entries
+passwd [--keyfile <path>]
+                     Change the password of the key slot it opens
+rekey [opts]         Re-derive key with new KDF parameters or cipher,
+                     --keyfile <path> adds a key file
+slots                List key slots that can unlock the vault
+slot add [opts]      Add a key slot with another password, takes the
+                     KDF options of create and --keyfile <path>
+slot rm <n>          Remove key slot n (members: see member rm)
+recovery rotate      Replace the recovery key and commit
+member keygen        Create a member identity at ~/.vault/identity
+                     ($VAULT_IDENTITY overrides the path)
+member pubkey        Show the public key of your identity
+member add <pubkey> <name>
+                     Grant a member access with their public key
+member rm <n> [--keyfile <path>]
+                     Remove member slot n and re-key the vault
+ls [folder]          List vaults or entries (--tag <tag> to filter)
+gen [len] [opts]     Generate password (--no-symbols, --no-digits,
+                     --no-upper, --no-lower, --no-ambiguous, --words <n>)
 clear                Clear terminal
 help                 Show help
 exit                 Exit
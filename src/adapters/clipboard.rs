@@ -0,0 +1,212 @@
+use std::{
+    env,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::domain::{errors::ClipboardError, ports::ClipboardPort};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 20;
+
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/* =======================
+   OSC 52 BACKEND
+======================= */
+
+// Asks the terminal emulator to set the system clipboard, works over SSH
+pub struct Osc52Clipboard;
+
+impl ClipboardPort for Osc52Clipboard {
+    fn set(&mut self, text: &str) -> Result<(), ClipboardError> {
+        let mut out = io::stdout();
+        write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), ClipboardError> {
+        self.set("")
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = u32::from_be_bytes([0, b[0], b[1], b[2]]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/* =======================
+   COMMAND BACKEND
+======================= */
+
+// Pipes the text into an external program, e.g. `wl-copy` or `xclip -selection clipboard`
+pub struct CommandClipboard {
+    command: String,
+}
+
+impl CommandClipboard {
+    pub fn new(command: String) -> Self {
+        Self { command }
+    }
+}
+
+impl ClipboardPort for CommandClipboard {
+    fn set(&mut self, text: &str) -> Result<(), ClipboardError> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(ClipboardError::CommandFailed(self.command.clone()));
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), ClipboardError> {
+        self.set("")
+    }
+}
+
+/* =======================
+   FILE BACKEND
+======================= */
+
+// Writes the clipboard content to a file, meant for testing
+pub struct FileClipboard {
+    path: PathBuf,
+}
+
+impl FileClipboard {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl ClipboardPort for FileClipboard {
+    fn set(&mut self, text: &str) -> Result<(), ClipboardError> {
+        fs::write(&self.path, text)?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), ClipboardError> {
+        self.set("")
+    }
+}
+
+/* =======================
+   AUTO CLEARING
+======================= */
+pub type SharedClipboard = Arc<Mutex<Box<dyn ClipboardPort + Send>>>;
+
+// Clears the clipboard `timeout` after the last copy
+pub struct ClipboardTimer {
+    backend: SharedClipboard,
+    timeout: Duration,
+    generation: Arc<AtomicU64>,
+    pending: bool,
+}
+
+impl ClipboardTimer {
+    pub fn new(backend: Box<dyn ClipboardPort + Send>, timeout: Duration) -> Self {
+        Self {
+            backend: Arc::new(Mutex::new(backend)),
+            timeout,
+            generation: Arc::new(AtomicU64::new(0)),
+            pending: false,
+        }
+    }
+
+    // Backend from VAULT_CLIPBOARD_FILE, VAULT_CLIPBOARD_CMD or OSC 52,
+    // timeout from VAULT_CLIPBOARD_TIMEOUT (seconds)
+    pub fn from_env() -> Self {
+        let backend: Box<dyn ClipboardPort + Send> = if let Ok(path) = env::var("VAULT_CLIPBOARD_FILE") {
+            Box::new(FileClipboard::new(path.into()))
+        } else if let Ok(cmd) = env::var("VAULT_CLIPBOARD_CMD") {
+            Box::new(CommandClipboard::new(cmd))
+        } else {
+            Box::new(Osc52Clipboard)
+        };
+
+        let timeout = env::var("VAULT_CLIPBOARD_TIMEOUT")
+            .ok()
+            .and_then(|t| t.parse().ok())
+            .unwrap_or(DEFAULT_TIMEOUT_SECS);
+
+        Self::new(backend, Duration::from_secs(timeout))
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn copy(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.backend
+            .lock()
+            .map_err(|_| ClipboardError::Poisoned)?
+            .set(text)?;
+        self.pending = true;
+
+        // Only the timer of the latest copy may clear
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let current = Arc::clone(&self.generation);
+        let backend = Arc::clone(&self.backend);
+        let timeout = self.timeout;
+
+        thread::spawn(move || {
+            thread::sleep(timeout);
+            if current.load(Ordering::SeqCst) == generation
+                && let Ok(mut backend) = backend.lock()
+            {
+                backend.clear().ok();
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn clear(&mut self) -> Result<(), ClipboardError> {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.pending = false;
+        self.backend
+            .lock()
+            .map_err(|_| ClipboardError::Poisoned)?
+            .clear()
+    }
+}
+
+impl Drop for ClipboardTimer {
+    // Pending timers die with the process, so clear on the way out
+    fn drop(&mut self) {
+        if self.pending {
+            self.clear().ok();
+        }
+    }
+}
@@ -2,7 +2,7 @@ use sha2::{Digest, Sha256};
 use std::{
     fs::{self, File, create_dir_all},
     io::{BufReader, Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use crate::domain::{errors::StorageError, ports::StoragePort};
@@ -87,6 +87,10 @@ impl StoragePort for FileStorage {
         self.path.exists()
     }
 
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
     fn list_vaults(&self) -> Result<Vec<String>, StorageError> {
         // Unrecoverable error
         let home = dirs_2::home_dir().expect("Error: Could not found home dir!");
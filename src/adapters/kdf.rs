@@ -0,0 +1,50 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use hmac::{Hmac, Mac, digest::KeyInit};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+use crate::domain::{
+    errors::CryptoError,
+    models::{Credentials, EntryId, KdfAlgorithm, KdfParams},
+};
+
+const ENTRY_KEY_CONTEXT: &[u8] = b"private-vault-entry-key";
+
+// Argon2 key derivation shared by the cipher adapters
+pub fn derive_key(
+    creds: &Credentials,
+    salt: &[u8],
+    kdf: &KdfParams,
+) -> Result<[u8; 32], CryptoError> {
+    let algorithm = match kdf.algorithm {
+        KdfAlgorithm::Argon2d => Algorithm::Argon2d,
+        KdfAlgorithm::Argon2i => Algorithm::Argon2i,
+        KdfAlgorithm::Argon2id => Algorithm::Argon2id,
+    };
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|_| CryptoError::InvalidKdfParams)?;
+
+    // The key file digest enters Argon2 as its secret input
+    let mut secret: Option<[u8; 32]> = creds.keyfile.map(|k| Sha256::digest(k).into());
+    let argon2 = match &secret {
+        Some(secret) => Argon2::new_with_secret(secret, algorithm, Version::V0x13, params)
+            .map_err(|_| CryptoError::KeyDerivationError)?,
+        None => Argon2::new(algorithm, Version::V0x13, params),
+    };
+
+    let mut output_key = [0u8; 32];
+    let res = argon2.hash_password_into(creds.password.as_bytes(), salt, &mut output_key);
+    secret.zeroize();
+    res.map_err(|_| CryptoError::KeyDerivationError)?;
+
+    Ok(output_key)
+}
+
+// HMAC-SHA256 of the entry id under the data key
+pub fn entry_key(key: &[u8; 32], id: EntryId) -> Result<[u8; 32], CryptoError> {
+    let mut mac =
+        <Hmac<Sha256> as KeyInit>::new_from_slice(key).map_err(|_| CryptoError::InvalidKey)?;
+    mac.update(ENTRY_KEY_CONTEXT);
+    mac.update(&id.to_le_bytes());
+    Ok(mac.finalize().into_bytes().into())
+}
@@ -0,0 +1,54 @@
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::domain::{errors::StorageError, ports::StoragePort};
+
+// A single vault file kept in memory for tests. Clones share the file, so a
+// test can read and replace what the engine saved
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    file: Rc<RefCell<Option<Vec<u8>>>>,
+    path: PathBuf,
+}
+
+impl MemoryStorage {
+    pub fn with_file(bytes: Vec<u8>) -> Self {
+        let storage = Self::default();
+        storage.file.replace(Some(bytes));
+        storage
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        self.file.borrow().clone().unwrap_or_default()
+    }
+}
+
+impl StoragePort for MemoryStorage {
+    fn exists(&self) -> bool {
+        self.file.borrow().is_some()
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn set_path(&mut self, path: String) {
+        self.path = path.into();
+    }
+
+    fn load(&self) -> Result<Vec<u8>, StorageError> {
+        self.file.borrow().clone().ok_or(StorageError::IntegrityError)
+    }
+
+    fn save(&self, data: &[u8]) -> Result<(), StorageError> {
+        self.file.replace(Some(data.to_vec()));
+        Ok(())
+    }
+
+    fn list_vaults(&self) -> Result<Vec<String>, StorageError> {
+        Ok(vec![])
+    }
+}
@@ -1,3 +1,12 @@
 pub mod file_storage;
-pub mod aes_crypto;
+#[cfg(test)]
+pub mod memory_storage;
+pub mod aead_crypto;
+pub mod kdf;
+pub mod suite_crypto;
 pub mod cli;
+pub mod clipboard;
+pub mod oneshot;
+pub mod password_gen;
+pub mod recipient;
+pub mod recovery_key;
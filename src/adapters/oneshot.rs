@@ -0,0 +1,365 @@
+use anyhow::{Result, anyhow};
+use std::{
+    fs,
+    io::{self, BufRead, IsTerminal},
+    process::ExitCode,
+};
+use zeroize::Zeroize;
+
+use crate::{
+    application::engine::VaultEngine,
+    domain::{
+        errors::VaultError,
+        models::{CipherSuite, Credentials, KdfParams},
+        ports::{CryptoPort, StoragePort},
+    },
+};
+
+/* =======================
+   EXIT CODES
+======================= */
+const EXIT_OK: u8 = 0;
+const EXIT_FAILURE: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+
+const PASSWORD_FD_ENV: &str = "VAULT_PASSWORD_FD";
+
+fn exit_code(e: &VaultError) -> u8 {
+    match e {
+        VaultError::Locked => 10,
+        VaultError::Unlocked => 11,
+        VaultError::EntryExists => 12,
+        VaultError::EntryNotFound => 13,
+        VaultError::VaultNotFound => 14,
+        VaultError::Serialization => 15,
+        VaultError::InvalidPassword => 16,
+        VaultError::Crypto(_) => 17,
+        VaultError::Storage(_) => 18,
+        VaultError::HistoryNotFound(_) => 19,
+        VaultError::AmbiguousEntry(_) => 20,
+        VaultError::Totp(_) => 21,
+        VaultError::UnsupportedVersion { .. } => 22,
+        VaultError::HeaderTampered => 23,
+        VaultError::KeyfileRequired => 24,
+        VaultError::KeySlotNotFound(_) => 25,
+        VaultError::LastKeySlot => 26,
+        VaultError::NoRecoveryKey => 27,
+        VaultError::NotAMember => 28,
+        VaultError::MemberSlot(_) => 29,
+        VaultError::VaultExists => 30,
+    }
+}
+
+/* =======================
+   PASSWORD SOURCE
+======================= */
+enum PasswordSource {
+    Tty,
+    Fd(i32),
+}
+
+impl PasswordSource {
+    fn read(&self, label: &str) -> Result<String> {
+        match self {
+            PasswordSource::Tty => Ok(rpassword::prompt_password(label)?),
+            PasswordSource::Fd(fd) => read_fd_line(*fd),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn read_fd_line(fd: i32) -> Result<String> {
+    use std::{fs::File, io::Read, mem::ManuallyDrop, os::unix::io::FromRawFd};
+
+    // The fd is owned by the caller, so it must not be closed here
+    let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+
+    // One byte at a time, so input after the first line stays unread for later prompts
+    let mut bytes = Vec::new();
+    let mut byte = [0u8; 1];
+    while file.read(&mut byte)? == 1 && byte[0] != b'\n' {
+        bytes.push(byte[0]);
+    }
+    if bytes.last() == Some(&b'\r') {
+        bytes.pop();
+    }
+
+    String::from_utf8(bytes).map_err(|e| {
+        e.into_bytes().zeroize();
+        anyhow!("Password is not valid UTF-8")
+    })
+}
+
+#[cfg(not(unix))]
+fn read_fd_line(_fd: i32) -> Result<String> {
+    Err(anyhow!("Reading passwords from a file descriptor is not supported on this platform"))
+}
+
+// Where the master credentials come from
+struct Secrets {
+    source: PasswordSource,
+    keyfile: Option<String>,
+}
+
+impl Secrets {
+    fn read_keyfile(&self) -> Result<Option<Vec<u8>>> {
+        self.keyfile
+            .as_deref()
+            .map(|p| fs::read(p).map_err(|e| anyhow!("Cannot read key file '{}': {}", p, e)))
+            .transpose()
+    }
+}
+
+/* =======================
+   COMMAND ENUM
+======================= */
+enum Command {
+    Ls(Option<String>),
+    Get {
+        vault: String,
+        service: String,
+        username: Option<String>,
+    },
+    Create(String),
+    Remove {
+        vault: String,
+        service: String,
+        username: Option<String>,
+    },
+    Add { vault: String, service: String, username: String },
+}
+
+/* =======================
+   ONE-SHOT STRUCT
+======================= */
+pub struct VaultOneShot<S: StoragePort, C: CryptoPort> {
+    engine: VaultEngine<S, C>,
+}
+
+/* =======================
+   IMPLEMENTATION
+======================= */
+impl<S: StoragePort, C: CryptoPort> VaultOneShot<S, C> {
+    pub fn new(engine: VaultEngine<S, C>) -> Self {
+        Self { engine }
+    }
+
+    pub fn run(mut self, args: Vec<String>) -> ExitCode {
+        let (cmd, secrets) = match Self::parse_args(args) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                eprintln!("Error: {:#}\n", e);
+                Self::print_usage();
+                return ExitCode::from(EXIT_USAGE);
+            }
+        };
+
+        match self.handle_command(cmd, &secrets) {
+            Ok(()) => ExitCode::from(EXIT_OK),
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                let code = e
+                    .downcast_ref::<VaultError>()
+                    .map(exit_code)
+                    .unwrap_or(EXIT_FAILURE);
+                ExitCode::from(code)
+            }
+        }
+    }
+
+    /* =======================
+       ARGUMENT PARSING
+    ======================= */
+    fn parse_args(args: Vec<String>) -> Result<(Command, Secrets)> {
+        let mut source = match std::env::var(PASSWORD_FD_ENV) {
+            Ok(fd) => PasswordSource::Fd(Self::parse_fd(&fd)?),
+            Err(_) => PasswordSource::Tty,
+        };
+
+        let mut keyfile = None;
+
+        // Split flags from positional arguments
+        let mut positional = Vec::new();
+        let mut it = args.into_iter();
+        while let Some(arg) = it.next() {
+            match arg.as_str() {
+                "--password-fd" => {
+                    let fd = it.next().ok_or(anyhow!("--password-fd requires a value"))?;
+                    source = PasswordSource::Fd(Self::parse_fd(&fd)?);
+                }
+                "--keyfile" => {
+                    keyfile = Some(it.next().ok_or(anyhow!("--keyfile requires a value"))?);
+                }
+                _ => positional.push(arg),
+            }
+        }
+
+        let mut p = positional.into_iter();
+        let cmd = p.next().ok_or(anyhow!("Missing command"))?;
+        let mut next = |what: &str| p.next().ok_or(anyhow!("Missing argument <{}>", what));
+
+        let cmd = match cmd.as_str() {
+            "ls" | "list" => Command::Ls(next("vault").ok()),
+            "get" => Command::Get {
+                vault: next("vault")?,
+                service: next("svc")?,
+                username: next("user").ok(),
+            },
+            "create" => Command::Create(next("vault")?),
+            "rm" => Command::Remove {
+                vault: next("vault")?,
+                service: next("svc")?,
+                username: next("user").ok(),
+            },
+            "add" => Command::Add {
+                vault: next("vault")?,
+                service: next("svc")?,
+                username: next("user")?,
+            },
+            other => return Err(anyhow!("Unknown command '{}'", other)),
+        };
+
+        Ok((cmd, Secrets { source, keyfile }))
+    }
+
+    fn parse_fd(value: &str) -> Result<i32> {
+        value
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Invalid file descriptor '{}'", value))
+    }
+
+    /* =======================
+       COMMAND HANDLER
+    ======================= */
+    fn handle_command(&mut self, cmd: Command, secrets: &Secrets) -> Result<()> {
+        match cmd {
+            Command::Ls(None) => {
+                for v in self.engine.get_vaults()? {
+                    println!("{}", v);
+                }
+            }
+
+            Command::Ls(Some(vault)) => {
+                self.unlock(&vault, secrets)?;
+                for e in self.engine.get_entries()? {
+                    println!("{}\t{}", e.service, e.username);
+                }
+            }
+
+            Command::Get {
+                vault,
+                service,
+                username,
+            } => {
+                self.unlock(&vault, secrets)?;
+                let id = self.engine.lookup(&service, username.as_deref())?;
+                let mut e = self.engine.get(id)?;
+                println!("{}", e.passwd);
+                e.zeroize();
+            }
+
+            Command::Create(vault) => {
+                let mut key = secrets.read_keyfile()?;
+                let mut pw = secrets.source.read("New vault password: ")?;
+                // A typo at the prompt would leave the new vault unopenable
+                if let PasswordSource::Tty = secrets.source {
+                    let mut repeat = secrets.source.read("Repeat vault password: ")?;
+                    let matches = pw == repeat;
+                    repeat.zeroize();
+                    if !matches {
+                        pw.zeroize();
+                        key.zeroize();
+                        return Err(anyhow!("Passwords do not match"));
+                    }
+                }
+                let res = self.engine.create_vault(
+                    &vault,
+                    &Credentials::new(&pw, key.as_deref()),
+                    KdfParams::default(),
+                    CipherSuite::default(),
+                    None,
+                );
+                pw.zeroize();
+                key.zeroize();
+                res?;
+                self.engine.commit()?;
+            }
+
+            Command::Add {
+                vault,
+                service,
+                username,
+            } => {
+                self.unlock(&vault, secrets)?;
+                let mut pw = Self::read_entry_password()?;
+                let res = self.engine.add(&service, &username, &pw);
+                pw.zeroize();
+                res?;
+                self.engine.commit()?;
+            }
+
+            Command::Remove {
+                vault,
+                service,
+                username,
+            } => {
+                self.unlock(&vault, secrets)?;
+                let id = self.engine.lookup(&service, username.as_deref())?;
+                self.engine.delete(id)?;
+                self.engine.commit()?;
+            }
+        }
+        Ok(())
+    }
+
+    /* =======================
+       UTIL
+    ======================= */
+    fn unlock(&mut self, vault: &str, secrets: &Secrets) -> Result<()> {
+        let mut key = secrets.read_keyfile()?;
+        let mut pw = secrets.source.read("Vault password: ")?;
+        let res = self.engine.unlock(vault, &Credentials::new(&pw, key.as_deref()));
+        pw.zeroize();
+        key.zeroize();
+        Ok(res?)
+    }
+
+    fn read_entry_password() -> Result<String> {
+        // Piped input is read as-is so scripts can feed the entry password
+        let line = if io::stdin().is_terminal() {
+            rpassword::prompt_password("Service password: ")?
+        } else {
+            let mut line = String::new();
+            if io::stdin().lock().read_line(&mut line)? == 0 {
+                return Err(anyhow!("No service password on stdin"));
+            }
+            let len = line.trim_end_matches(['\r', '\n']).len();
+            line.truncate(len);
+            line
+        };
+
+        if line.is_empty() {
+            return Err(anyhow!("Service password must not be empty"));
+        }
+        Ok(line)
+    }
+
+    fn print_usage() {
+        eprintln!(
+            r#"Usage: vault [--password-fd <fd>] [--keyfile <path>] <command> [args]
+
+ls [vault]                  List vaults, or entries of a vault
+get <vault> <svc> [user]    Print entry password
+create <vault>              Create vault
+add <vault> <svc> <user>    Add entry and commit
+rm <vault> <svc> [user]     Remove entry and commit
+
+Without a command the interactive shell is started.
+The master password is read from the terminal, from --password-fd
+or from the fd named by ${PASSWORD_FD_ENV}. Vaults created with
+--keyfile need the same key file to be opened.
+"#
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use aes_gcm::aead::{OsRng, rand_core::RngCore};
+
+use crate::domain::errors::GeneratorError;
+
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!@#$%^&*()-_=+[]{};:,.<>?/~";
+const AMBIGUOUS: &str = "Il1O0o|`'\";:,.";
+
+// BIP39 english wordlist, 2048 words (11 bits each)
+pub const WORDLIST: &str = include_str!("wordlist.txt");
+
+pub struct GeneratorOptions {
+    pub length: usize,
+    pub lower: bool,
+    pub upper: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    pub exclude_ambiguous: bool,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self {
+            length: 20,
+            lower: true,
+            upper: true,
+            digits: true,
+            symbols: true,
+            exclude_ambiguous: false,
+        }
+    }
+}
+
+pub fn generate_password(opts: &GeneratorOptions) -> Result<String, GeneratorError> {
+    let classes: Vec<Vec<char>> = [
+        (opts.lower, LOWER),
+        (opts.upper, UPPER),
+        (opts.digits, DIGITS),
+        (opts.symbols, SYMBOLS),
+    ]
+    .iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, set)| {
+        set.chars()
+            .filter(|c| !opts.exclude_ambiguous || !AMBIGUOUS.contains(*c))
+            .collect()
+    })
+    .collect();
+
+    if classes.is_empty() {
+        return Err(GeneratorError::NoCharacterClass);
+    }
+    if opts.length < classes.len() {
+        return Err(GeneratorError::TooShort(classes.len()));
+    }
+
+    // One character of each class, then fill from the union
+    let alphabet: Vec<char> = classes.iter().flatten().copied().collect();
+    let mut chars: Vec<char> = classes.iter().map(|set| pick(set)).collect();
+    while chars.len() < opts.length {
+        chars.push(pick(&alphabet));
+    }
+
+    shuffle(&mut chars);
+    Ok(chars.into_iter().collect())
+}
+
+pub fn generate_passphrase(words: usize, separator: &str) -> Result<String, GeneratorError> {
+    if words == 0 {
+        return Err(GeneratorError::TooShort(1));
+    }
+
+    let list: Vec<&str> = WORDLIST.lines().collect();
+    let phrase: Vec<&str> = (0..words).map(|_| pick(&list)).collect();
+
+    Ok(phrase.join(separator))
+}
+
+/* =======================
+   RANDOMNESS
+======================= */
+
+// Uniform index in [0, n) without modulo bias
+fn random_below(n: usize) -> usize {
+    let n = n as u64;
+    let limit = u64::MAX - u64::MAX % n;
+    loop {
+        let v = OsRng.next_u64();
+        if v < limit {
+            return (v % n) as usize;
+        }
+    }
+}
+
+fn pick<T: Copy>(set: &[T]) -> T {
+    set[random_below(set.len())]
+}
+
+// Fisher-Yates
+fn shuffle<T>(items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, random_below(i + 1));
+    }
+}
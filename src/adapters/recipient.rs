@@ -0,0 +1,144 @@
+use aes_gcm::aead::{OsRng, rand_core::RngCore};
+use anyhow::{Result, anyhow};
+use sha2::{Digest, Sha256};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroize;
+
+use crate::domain::errors::CryptoError;
+
+const IDENTITY_ENV: &str = "VAULT_IDENTITY";
+const KEK_CONTEXT: &[u8] = b"private-vault-member-key";
+
+/* =======================
+   KEY AGREEMENT
+======================= */
+pub fn generate_identity() -> ([u8; 32], [u8; 32]) {
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+    let public = public_key(&secret);
+    (secret, public)
+}
+
+pub fn public_key(secret: &[u8; 32]) -> [u8; 32] {
+    PublicKey::from(&StaticSecret::from(*secret)).to_bytes()
+}
+
+// Wrapping key for a recipient, with the ephemeral public key sent along
+pub fn sender_key(recipient: &[u8; 32]) -> Result<([u8; 32], [u8; 32]), CryptoError> {
+    let (mut ephemeral, ephemeral_public) = generate_identity();
+    let kek = derive_kek(&ephemeral, recipient, &ephemeral_public, recipient);
+    ephemeral.zeroize();
+    Ok((ephemeral_public, kek?))
+}
+
+// The same wrapping key, recomputed by the recipient
+pub fn recipient_key(secret: &[u8; 32], ephemeral: &[u8; 32]) -> Result<[u8; 32], CryptoError> {
+    derive_kek(secret, ephemeral, ephemeral, &public_key(secret))
+}
+
+// Both public keys are hashed in so the key is bound to this exchange
+fn derive_kek(
+    secret: &[u8; 32],
+    peer: &[u8; 32],
+    ephemeral: &[u8; 32],
+    recipient: &[u8; 32],
+) -> Result<[u8; 32], CryptoError> {
+    let shared = StaticSecret::from(*secret).diffie_hellman(&PublicKey::from(*peer));
+
+    // Low order points give a predictable shared secret
+    if !shared.was_contributory() {
+        return Err(CryptoError::InvalidPublicKey);
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(KEK_CONTEXT);
+    hasher.update(shared.as_bytes());
+    hasher.update(ephemeral);
+    hasher.update(recipient);
+    Ok(hasher.finalize().into())
+}
+
+/* =======================
+   ENCODING
+======================= */
+pub fn encode_key(key: &[u8; 32]) -> String {
+    key.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn decode_key(hex: &str) -> Option<[u8; 32]> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+/* =======================
+   IDENTITY FILE
+======================= */
+
+// $VAULT_IDENTITY, or next to the vaults
+pub fn identity_path() -> PathBuf {
+    match std::env::var(IDENTITY_ENV) {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => dirs_2::home_dir()
+            .unwrap_or_default()
+            .join(".vault")
+            .join("identity"),
+    }
+}
+
+pub fn load_identity() -> Result<[u8; 32]> {
+    let path = identity_path();
+    let mut text = fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Cannot read identity '{}': {}", path.display(), e))?;
+    let secret = decode_key(&text);
+    text.zeroize();
+    secret.ok_or(anyhow!("Invalid identity file '{}'", path.display()))
+}
+
+// Writes a new identity and returns its public key
+pub fn create_identity() -> Result<[u8; 32]> {
+    let path = identity_path();
+    if path.exists() {
+        return Err(anyhow!("Identity '{}' already exists", path.display()));
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let (mut secret, public) = generate_identity();
+    let mut text = encode_key(&secret);
+    secret.zeroize();
+    let res = write_private(&path, text.as_bytes());
+    text.zeroize();
+    res?;
+
+    Ok(public)
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(data)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    fs::write(path, data)
+}
@@ -0,0 +1,115 @@
+use aes_gcm::aead::{OsRng, rand_core::RngCore};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+use crate::{adapters::password_gen::WORDLIST, domain::errors::RecoveryKeyError};
+
+// 256 bits of entropy and an 8 bit checksum, 11 bits per word as in BIP39
+const ENTROPY_LEN: usize = 32;
+pub const WORDS: usize = 24;
+
+pub fn generate() -> String {
+    let mut entropy = [0u8; ENTROPY_LEN];
+    OsRng.fill_bytes(&mut entropy);
+
+    let list: Vec<&str> = WORDLIST.lines().collect();
+    let checksum = Sha256::digest(entropy)[0];
+
+    let mut words = Vec::with_capacity(WORDS);
+    let (mut acc, mut bits) = (0u32, 0);
+    for byte in entropy.iter().chain([checksum].iter()) {
+        acc = (acc << 8) | *byte as u32;
+        bits += 8;
+        if bits >= 11 {
+            bits -= 11;
+            words.push(list[(acc >> bits) as usize & 0x7ff]);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    entropy.zeroize();
+
+    words.join(" ")
+}
+
+// Canonical form of a typed recovery key, whatever its case and spacing
+pub fn normalize(input: &str) -> Result<String, RecoveryKeyError> {
+    let list: Vec<&str> = WORDLIST.lines().collect();
+    let mut words: Vec<String> = input.split_whitespace().map(str::to_lowercase).collect();
+    if words.len() != WORDS {
+        return Err(RecoveryKeyError::Length {
+            expected: WORDS,
+            found: words.len(),
+        });
+    }
+
+    let mut bytes = Vec::with_capacity(ENTROPY_LEN + 1);
+    let (mut acc, mut bits) = (0u32, 0);
+    for word in &words {
+        let index = list
+            .iter()
+            .position(|w| w == word)
+            .ok_or_else(|| RecoveryKeyError::UnknownWord(word.clone()))?;
+        acc = (acc << 11) | index as u32;
+        bits += 11;
+        while bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+        }
+        acc &= (1 << bits) - 1;
+    }
+
+    let checksum = bytes.pop();
+    let valid = checksum == Some(Sha256::digest(&bytes)[0]);
+    bytes.zeroize();
+    if !valid {
+        return Err(RecoveryKeyError::Checksum);
+    }
+
+    let key = words.join(" ");
+    words.zeroize();
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Bytes 0..32 as entropy, encoded with the BIP39 word list
+    const KEY: &str = "abandon amount liar amount expire adjust cage candy arch gather drum bullet \
+                       absurd math era live bid rhythm alien crouch range attend journey unaware";
+
+    #[test]
+    fn generated_keys_round_trip() {
+        for _ in 0..32 {
+            let key = generate();
+            assert_eq!(key.split(' ').count(), WORDS);
+            assert_eq!(normalize(&key).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn normalize_ignores_case_and_spacing() {
+        let typed = format!("  {}\n", KEY.to_uppercase().replace(' ', "   "));
+        assert_eq!(normalize(&typed).unwrap(), KEY);
+    }
+
+    #[test]
+    fn flipped_word_fails_checksum() {
+        let flipped = KEY.replacen("abandon", "ability", 1);
+        assert!(matches!(normalize(&flipped), Err(RecoveryKeyError::Checksum)));
+    }
+
+    #[test]
+    fn wrong_word_count_fails_length() {
+        let short: Vec<&str> = KEY.split(' ').take(WORDS - 1).collect();
+        let res = normalize(&short.join(" "));
+        assert!(matches!(res, Err(RecoveryKeyError::Length { expected: WORDS, found: 23 })));
+        assert!(matches!(normalize(""), Err(RecoveryKeyError::Length { found: 0, .. })));
+    }
+
+    #[test]
+    fn unknown_word_is_reported() {
+        let typo = KEY.replacen("liar", "lair", 1);
+        assert!(matches!(normalize(&typo), Err(RecoveryKeyError::UnknownWord(w)) if w == "lair"));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{
+    adapters::aead_crypto::{AesGcmCrypto, XChaCha20Poly1305Crypto},
+    domain::{
+        errors::CryptoError,
+        models::{CipherSuite, Credentials, EntryId, KdfParams},
+        ports::CryptoPort,
+        secure_memory::SecureBuffer,
+    },
+};
+
+// Dispatches to the cipher recorded in each vault's header
+pub struct SuiteCrypto {
+    aes: AesGcmCrypto,
+    xchacha: XChaCha20Poly1305Crypto,
+    suite: CipherSuite,
+}
+
+impl SuiteCrypto {
+    pub fn new() -> Self {
+        Self {
+            aes: AesGcmCrypto::new(),
+            xchacha: XChaCha20Poly1305Crypto::new(),
+            suite: CipherSuite::default(),
+        }
+    }
+
+    fn active(&self) -> &dyn CryptoPort {
+        match self.suite {
+            CipherSuite::Aes256Gcm => &self.aes,
+            CipherSuite::XChaCha20Poly1305 => &self.xchacha,
+        }
+    }
+
+    fn active_mut(&mut self) -> &mut dyn CryptoPort {
+        match self.suite {
+            CipherSuite::Aes256Gcm => &mut self.aes,
+            CipherSuite::XChaCha20Poly1305 => &mut self.xchacha,
+        }
+    }
+}
+
+impl CryptoPort for SuiteCrypto {
+    // Switching suites drops the data key
+    fn select(&mut self, suite: CipherSuite) -> Result<(), CryptoError> {
+        if suite != self.suite {
+            self.clear();
+            self.suite = suite;
+        }
+        Ok(())
+    }
+
+    fn init(
+        &mut self,
+        creds: &Credentials,
+        salt: &[u8],
+        kdf: &KdfParams,
+    ) -> Result<(), CryptoError> {
+        self.active_mut().init(creds, salt, kdf)
+    }
+
+    fn generate_key(&mut self) -> Result<(), CryptoError> {
+        self.active_mut().generate_key()
+    }
+
+    fn wrap_key(
+        &self,
+        creds: &Credentials,
+        salt: &[u8],
+        kdf: &KdfParams,
+    ) -> Result<Vec<u8>, CryptoError> {
+        self.active().wrap_key(creds, salt, kdf)
+    }
+
+    fn unwrap_key(
+        &mut self,
+        wrapped: &[u8],
+        creds: &Credentials,
+        salt: &[u8],
+        kdf: &KdfParams,
+    ) -> Result<(), CryptoError> {
+        self.active_mut().unwrap_key(wrapped, creds, salt, kdf)
+    }
+
+    fn generate_identity(&self) -> ([u8; 32], [u8; 32]) {
+        self.active().generate_identity()
+    }
+
+    fn wrap_key_for(&self, public_key: &[u8; 32]) -> Result<Vec<u8>, CryptoError> {
+        self.active().wrap_key_for(public_key)
+    }
+
+    fn unwrap_key_with(&mut self, wrapped: &[u8], secret: &[u8; 32]) -> Result<(), CryptoError> {
+        self.active_mut().unwrap_key_with(wrapped, secret)
+    }
+
+    fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<(Vec<u8>, Vec<u8>), CryptoError> {
+        self.active().encrypt(plaintext, aad)
+    }
+
+    fn decrypt(
+        &self,
+        ciphertext: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+    ) -> Result<SecureBuffer, CryptoError> {
+        self.active().decrypt(ciphertext, nonce, aad)
+    }
+
+    fn encrypt_entry(
+        &self,
+        id: EntryId,
+        plaintext: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), CryptoError> {
+        self.active().encrypt_entry(id, plaintext)
+    }
+
+    fn decrypt_entry(
+        &self,
+        id: EntryId,
+        ciphertext: &[u8],
+        nonce: &[u8],
+    ) -> Result<SecureBuffer, CryptoError> {
+        self.active().decrypt_entry(id, ciphertext, nonce)
+    }
+
+    fn clear(&mut self) {
+        self.aes.clear();
+        self.xchacha.clear();
+    }
+
+    fn salt_gen(&self) -> [u8; 16] {
+        self.active().salt_gen()
+    }
+}
@@ -1,16 +1,57 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
 
 use zeroize::Zeroize;
 
-use crate::domain::{
-    errors::VaultError, models::{Entry, VaultState}, ports::{CryptoPort, StoragePort}
+use crate::{
+    application::{migrations, search::fuzzy_score, totp},
+    domain::{
+        errors::{CryptoError, TotpError, VaultError},
+        models::{
+            CipherSuite, Credentials, Entry, EntryFilter, EntryId, EntryIndex, EntryUpdate,
+            IdlePolicy, KdfParams, KeySlot, Member, PasswordRecord, SealedEntry, SlotKind,
+            VaultState,
+        },
+        ports::{CryptoPort, StoragePort},
+        secure_memory::{CoreDumpGuard, SecureBuffer},
+    },
 };
 
+const KEY_CHECK: &[u8] = b"private-vault-key-check";
+
+// What a key slot is opened with
+#[derive(Clone, Copy)]
+enum SlotKey<'a> {
+    Password(Credentials<'a>),
+    Recovery(Credentials<'a>),
+    Identity(&'a [u8; 32]),
+}
+
+impl SlotKey<'_> {
+    fn kind(&self) -> SlotKind {
+        match self {
+            SlotKey::Password(_) => SlotKind::Password,
+            SlotKey::Recovery(_) => SlotKind::Recovery,
+            SlotKey::Identity(_) => SlotKind::Member,
+        }
+    }
+}
+
 pub struct VaultEngine<S: StoragePort, C: CryptoPort> {
     storage: S,
     crypto: C,
     vault_state: Option<VaultState>,
-    entries: BTreeMap<String, Entry>,
+    vault_name: Option<String>,
+    // Entry secrets stay encrypted until asked for
+    entries: BTreeMap<EntryId, SealedEntry>,
+    // Never reused, entry subkeys are derived from the id
+    next_id: EntryId,
+    generation: u64,
+    migrated_from: Option<u16>,
+    // Held while unlocked
+    core_dumps: Option<CoreDumpGuard>,
 }
 
 impl<S: StoragePort, C: CryptoPort> VaultEngine<S, C> {
@@ -19,7 +60,12 @@ impl<S: StoragePort, C: CryptoPort> VaultEngine<S, C> {
             storage: storage,
             crypto: crypto,
             vault_state: None,
+            vault_name: None,
             entries: BTreeMap::new(),
+            next_id: 1,
+            generation: 0,
+            migrated_from: None,
+            core_dumps: None,
         }
     }
 
@@ -27,24 +73,64 @@ impl<S: StoragePort, C: CryptoPort> VaultEngine<S, C> {
         self.vault_state.is_none()
     }
 
-    pub fn current_vault(&self) -> Option<&str>{
-        Some("teste")
+    pub fn current_vault(&self) -> Option<&str> {
+        self.vault_name.as_deref()
     }
 
-    pub fn is_dirty(&self) -> bool{
-        true
+    // Resolved storage location of the open vault
+    pub fn current_path(&self) -> Option<&Path> {
+        self.vault_name.as_ref().map(|_| self.storage.path())
     }
 
-    pub fn create_vault(&mut self, name: &str, password: &str) -> Result<(), VaultError> {
+    pub fn is_dirty(&self) -> bool {
+        self.generation != 0
+    }
+
+    // Format version the open vault was upgraded from, until committed
+    pub fn migrated_from(&self) -> Option<u16> {
+        self.migrated_from
+    }
+
+    fn touch(&mut self) {
+        self.generation += 1;
+    }
+
+    pub fn create_vault(
+        &mut self,
+        name: &str,
+        creds: &Credentials,
+        kdf: KdfParams,
+        suite: CipherSuite,
+        recovery: Option<&Credentials>,
+    ) -> Result<(), VaultError> {
         if !self.is_locked() {
             return Err(VaultError::Unlocked);
         }
 
-        let salt = self.crypto.salt_gen();
-        self.vault_state = Some(VaultState::new(&salt));
-
+        // Never replace an existing vault with an empty one
         self.storage.set_path(name.into());
-        self.crypto.init(password, &salt)?;
+        if self.storage.exists() {
+            return Err(VaultError::VaultExists);
+        }
+
+        // Entries are encrypted under a random data key, wrapped by the first slot
+        self.crypto.select(suite)?;
+        self.crypto.generate_key()?;
+        let mut state = VaultState::new(suite, self.new_slot(creds, kdf, SlotKind::Password)?);
+        if let Some(recovery) = recovery {
+            let slot = self.new_slot(recovery, KdfParams::default(), SlotKind::Recovery)?;
+            state.slots.push(slot);
+        }
+
+        self.vault_state = Some(state);
+        self.core_dumps = Some(CoreDumpGuard::disable());
+
+        // A fresh vault only exists on disk after its first commit
+        self.vault_name = Some(name.into());
+        self.entries.clear();
+        self.next_id = 1;
+        self.generation = 0;
+        self.touch();
 
         Ok(())
     }
@@ -52,119 +138,875 @@ impl<S: StoragePort, C: CryptoPort> VaultEngine<S, C> {
     pub fn commit(&mut self) -> Result<(), VaultError> {
         let vault_state = self.vault_state.as_mut().ok_or(VaultError::Locked)?;
 
-        let mut entries_buffer = Vec::new();
-        wincode::serialize_into(&mut entries_buffer, &self.entries)
+        // Next id then entries, sized up front so the plaintext is never copied by a growing Vec
+        let size = wincode::serialized_size(&self.next_id)
+            .and_then(|id| Ok(id + wincode::serialized_size(&self.entries)?))
+            .map_err(|_| VaultError::Serialization)?;
+        let mut entries_buffer = SecureBuffer::new(size as usize);
+        let mut out = &mut entries_buffer[..];
+        wincode::serialize_into(&mut out, &self.next_id)
+            .and_then(|_| wincode::serialize_into(&mut out, &self.entries))
             .map_err(|_| VaultError::Serialization)?;
 
-        let (cipher, nonce) = self.crypto.encrypt(&entries_buffer)?;
+        let aad = migrations::header_aad(migrations::FORMAT_VERSION, vault_state)?;
+        let (cipher, nonce) = self.crypto.encrypt(&entries_buffer, &aad)?;
 
         vault_state.cipher = cipher;
         vault_state.nonce = nonce;
 
         let mut vault_buffer = Vec::new();
+        migrations::write_header(&mut vault_buffer);
         wincode::serialize_into(&mut vault_buffer, vault_state)
             .map_err(|_| VaultError::Serialization)?;
 
         self.storage.save(&vault_buffer)?;
+        self.generation = 0;
+        self.migrated_from = None;
 
         Ok(())
     }
 
-    pub fn unlock(&mut self, vault: &str, password: &str) -> Result<(), VaultError> {
-        self.storage.set_path(vault.into());
+    pub fn unlock(&mut self, vault: &str, creds: &Credentials) -> Result<(), VaultError> {
+        self.unlock_with(vault, SlotKey::Password(*creds))
+    }
 
-        if !self.storage.exists() {
-            return Err(VaultError::VaultNotFound);
-        }
+    // Opens the vault through its recovery slot instead of a password
+    pub fn unlock_recovery(
+        &mut self,
+        vault: &str,
+        recovery: &Credentials,
+    ) -> Result<(), VaultError> {
+        self.unlock_with(vault, SlotKey::Recovery(*recovery))
+    }
+
+    // Opens a shared vault through the member slot of an X25519 identity
+    pub fn unlock_identity(&mut self, vault: &str, secret: &[u8; 32]) -> Result<(), VaultError> {
+        self.unlock_with(vault, SlotKey::Identity(secret))
+    }
 
-        // Do not unlock if it's already unlocked
+    fn unlock_with(&mut self, vault: &str, key: SlotKey) -> Result<(), VaultError> {
+        // Do not unlock if it's already unlocked, nor touch the open vault's path
         if !self.is_locked() {
             return Err(VaultError::Unlocked);
         }
 
+        // Storage only counts as pointing at `vault` once vault_name is set below
+        self.storage.set_path(vault.into());
+        if !self.storage.exists() {
+            return Err(VaultError::VaultNotFound);
+        }
+
+        // No plaintext may end up in a core dump from here on
+        let core_dumps = CoreDumpGuard::disable();
+
         // Load file bytes
         let buffer = self.storage.load()?;
 
-        // Deserialize into vault state
-        let v_state: VaultState = wincode::deserialize_from(&mut buffer.as_slice())
-            .map_err(|_| VaultError::Serialization)?;
-        self.vault_state = Some(v_state.clone());
+        // Check header and upgrade the vault state to the current layout
+        let (version, body) = migrations::split_header(&buffer)?;
+        let mut v_state = migrations::read_state(version, body)?;
 
-        // Derive key
-        self.crypto.init(password, &v_state.salt)?;
+        // Recover the data key from any slot the key opens
+        self.crypto.select(v_state.suite)?;
+        let index = self.open_slot(version, &v_state.slots, key)?;
 
-        // Decrypt entries
-        // let v_state = self.vault_state.as_ref().ok_or(VaultError::Locked)?;
+        // Decrypt the index, the vault stays locked on failure
+        let entries = self
+            .open(version, &v_state)
+            .and_then(|stream| self.load_entries(version, &stream, &mut v_state, index, key))
+            .inspect_err(|_| self.crypto.clear())?;
 
-        let stream = self
-            .crypto
-            .decrypt(&v_state.cipher, &v_state.nonce)
-            .map_err(|_| {
-                self.vault_state = None;    //Invalid password, keeps vault locked
-                VaultError::InvalidPassword
-            });
+        (self.next_id, self.entries) = entries;
+        self.vault_state = Some(v_state);
+        self.core_dumps = Some(core_dumps);
+        self.vault_name = Some(vault.into());
+        self.generation = 0;
 
-        // Deserialize entries into BTreeMap
-        self.entries = wincode::deserialize_from(&mut stream?.as_slice())
-            .map_err(|_| VaultError::Serialization)?;
+        // Upgraded vaults are rewritten in the current format on next commit
+        self.migrated_from = None;
+        if version < migrations::FORMAT_VERSION {
+            self.migrated_from = Some(version);
+            self.touch();
+        }
+
+        Ok(())
+    }
+
+    // Vaults before v8 decrypt to whole entries, sealed once the data key is final
+    fn load_entries(
+        &mut self,
+        version: u16,
+        stream: &[u8],
+        state: &mut VaultState,
+        index: usize,
+        key: SlotKey,
+    ) -> Result<(EntryId, BTreeMap<EntryId, SealedEntry>), VaultError> {
+        if version >= migrations::SEALED_VERSION {
+            return migrations::read_index(version, stream);
+        }
+
+        let mut entries = migrations::read_entries(version, stream)?;
+        let res = if version < migrations::ENVELOPE_VERSION {
+            self.wrap_legacy_key(state, index, key)
+        } else {
+            Ok(())
+        };
+        let sealed = res.and_then(|_| self.seal_all(entries.values()));
+        let next_id = migrations::next_free_id(&entries);
+        for entry in entries.values_mut() {
+            entry.zeroize();
+        }
+        Ok((next_id, sealed?))
+    }
+
+    // Makes the data key of the first slot of the key's kind it opens current
+    fn open_slot(
+        &mut self,
+        version: u16,
+        slots: &[KeySlot],
+        key: SlotKey,
+    ) -> Result<usize, VaultError> {
+        let mut keyfile_missing = false;
+        let mut tried = false;
+
+        for (index, slot) in slots.iter().enumerate() {
+            if slot.kind != key.kind() {
+                continue;
+            }
+            tried = true;
+
+            let res = match key {
+                SlotKey::Identity(secret) => self.crypto.unwrap_key_with(&slot.wrapped, secret),
+                SlotKey::Password(creds) | SlotKey::Recovery(creds) => {
+                    let Some(creds) = Self::slot_creds(slot, &creds) else {
+                        keyfile_missing = true;
+                        continue;
+                    };
+
+                    // Before v5 the only slot derives the data key itself
+                    if version < migrations::ENVELOPE_VERSION {
+                        self.crypto.init(&creds, &slot.salt, &slot.kdf)?;
+                        return Ok(index);
+                    }
+
+                    self.crypto
+                        .unwrap_key(&slot.wrapped, &creds, &slot.salt, &slot.kdf)
+                }
+            };
+
+            match res {
+                Ok(()) => return Ok(index),
+                Err(CryptoError::Aead(_)) => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Err(match (keyfile_missing, tried, key.kind()) {
+            (true, _, _) => VaultError::KeyfileRequired,
+            (false, false, SlotKind::Recovery) => VaultError::NoRecoveryKey,
+            (false, _, SlotKind::Member) => VaultError::NotAMember,
+            _ => VaultError::InvalidPassword,
+        })
+    }
+
+    // Credentials as a slot expects them, None if its key file is missing
+    fn slot_creds<'a>(slot: &KeySlot, creds: &Credentials<'a>) -> Option<Credentials<'a>> {
+        match (slot.keyfile, creds.keyfile) {
+            (true, None) => None,
+            (true, Some(_)) => Some(*creds),
+            (false, _) => Some(Credentials::new(creds.password, None)),
+        }
+    }
+
+    // Older vaults used the derived key directly, move them to a random data key
+    fn wrap_legacy_key(
+        &mut self,
+        state: &mut VaultState,
+        index: usize,
+        key: SlotKey,
+    ) -> Result<(), VaultError> {
+        // Legacy vaults only have a password slot
+        let SlotKey::Password(creds) = key else {
+            return Err(VaultError::InvalidPassword);
+        };
+        let slot = &state.slots[index];
+        let creds = Self::slot_creds(slot, &creds).ok_or(VaultError::KeyfileRequired)?;
+        let kdf = slot.kdf;
 
+        self.crypto.generate_key()?;
+        state.slots = vec![self.new_slot(&creds, kdf, SlotKind::Password)?];
         Ok(())
     }
 
+    // Wraps the current data key for a new set of credentials
+    fn new_slot(
+        &self,
+        creds: &Credentials,
+        kdf: KdfParams,
+        kind: SlotKind,
+    ) -> Result<KeySlot, VaultError> {
+        let salt = self.crypto.salt_gen();
+        Ok(KeySlot {
+            kind,
+            kdf,
+            salt,
+            keyfile: creds.keyfile.is_some(),
+            member: None,
+            wrapped: self.crypto.wrap_key(creds, &salt, &kdf)?,
+        })
+    }
+
+    // Wraps the current data key for a member's public key
+    fn member_slot(&self, member: Member) -> Result<KeySlot, VaultError> {
+        Ok(KeySlot {
+            kind: SlotKind::Member,
+            kdf: KdfParams::default(),
+            salt: [0u8; 16],
+            keyfile: false,
+            wrapped: self.crypto.wrap_key_for(&member.public_key)?,
+            member: Some(member),
+        })
+    }
+
+    fn open(&self, version: u16, state: &VaultState) -> Result<SecureBuffer, VaultError> {
+        // Older vaults have neither key check nor associated data
+        if version < migrations::AAD_VERSION {
+            return self
+                .crypto
+                .decrypt(&state.cipher, &state.nonce, &[])
+                .map_err(|_| VaultError::InvalidPassword);
+        }
+
+        // Pre-envelope vaults check the derived key against a known plaintext
+        if version < migrations::ENVELOPE_VERSION {
+            let key_check = &state.slots.first().ok_or(VaultError::Serialization)?.wrapped;
+            let (check_nonce, check) = key_check
+                .split_at_checked(state.suite.nonce_len())
+                .ok_or(VaultError::Serialization)?;
+            let plain = self
+                .crypto
+                .decrypt(check, check_nonce, &[])
+                .map_err(|_| VaultError::InvalidPassword)?;
+            if &plain[..] != KEY_CHECK {
+                return Err(VaultError::InvalidPassword);
+            }
+        }
+
+        // The key is right, so a failure here means the header changed
+        let aad = migrations::header_aad(version, state)?;
+        self.crypto
+            .decrypt(&state.cipher, &state.nonce, &aad)
+            .map_err(|_| VaultError::HeaderTampered)
+    }
+
     pub fn lock(&mut self) -> Result<(), VaultError> {
         if self.is_locked() {
             return Err(VaultError::Locked);
         }
 
         for entry in self.entries.values_mut() {
-            entry.zeroize();
+            entry.index.zeroize();
         }
 
         self.entries.clear();
+        self.next_id = 1;
+        self.crypto.clear();
+        self.core_dumps = None;
         self.vault_state = None;
+        self.vault_name = None;
+        self.generation = 0;
+        self.migrated_from = None;
 
         Ok(())
     }
 
-    pub fn add(&mut self, service: &str, username: &str, password: &str) -> Result<(), VaultError> {
+    // Re-wraps the data key of the slot opened by `old`, other slots keep working
+    pub fn change_password(
+        &mut self,
+        old: &Credentials,
+        new: &Credentials,
+    ) -> Result<(), VaultError> {
+        let suite = self.suite()?;
+        self.rederive(old, new, |kdf| kdf, suite, None)
+    }
+
+    // Upgrades the KDF parameters of the opened slot and/or the cipher suite,
+    // keeping the password. `kdf` maps the slot's current parameters to the new ones.
+    // A new suite gets a fresh data key: other password slots are dropped, members
+    // are wrapped again and any recovery slot is replaced by one for `recovery`
+    pub fn rekey(
+        &mut self,
+        creds: &Credentials,
+        kdf: impl FnOnce(KdfParams) -> KdfParams,
+        suite: CipherSuite,
+        recovery: Option<&Credentials>,
+    ) -> Result<(), VaultError> {
+        self.rederive(creds, creds, kdf, suite, recovery)
+    }
+
+    pub fn suite(&self) -> Result<CipherSuite, VaultError> {
+        Ok(self.vault_state.as_ref().ok_or(VaultError::Locked)?.suite)
+    }
+
+    pub fn key_slots(&self) -> Result<&[KeySlot], VaultError> {
+        Ok(&self.vault_state.as_ref().ok_or(VaultError::Locked)?.slots)
+    }
+
+    // Lets another set of credentials unlock the vault, returns the slot number
+    pub fn add_slot(&mut self, creds: &Credentials, kdf: KdfParams) -> Result<usize, VaultError> {
         if self.is_locked() {
             return Err(VaultError::Locked);
         }
 
-        let entry = Entry::new(service.into(), username.into(), password.into());
+        let slot = self.new_slot(creds, kdf, SlotKind::Password)?;
+        let state = self.vault_state.as_mut().ok_or(VaultError::Locked)?;
+        state.slots.push(slot);
+        let n = state.slots.len();
+        self.touch();
+
+        Ok(n)
+    }
+
+    // Slots are numbered from 1, as listed
+    pub fn remove_slot(&mut self, n: usize) -> Result<(), VaultError> {
+        let state = self.vault_state.as_mut().ok_or(VaultError::Locked)?;
+        if n == 0 || n > state.slots.len() {
+            return Err(VaultError::KeySlotNotFound(n));
+        }
+        if state.slots.len() == 1 {
+            return Err(VaultError::LastKeySlot);
+        }
+        // A removed member must not read later commits, which takes a new data key
+        if state.slots[n - 1].kind == SlotKind::Member {
+            return Err(VaultError::MemberSlot(n));
+        }
+
+        state.slots.remove(n - 1);
+        self.touch();
+
+        Ok(())
+    }
+
+    // Lets a team member unlock the vault with their identity, returns the slot number
+    pub fn add_member(&mut self, name: &str, public_key: [u8; 32]) -> Result<usize, VaultError> {
+        if self.is_locked() {
+            return Err(VaultError::Locked);
+        }
+
+        let slot = self.member_slot(Member {
+            name: name.into(),
+            public_key,
+        })?;
+        let state = self.vault_state.as_mut().ok_or(VaultError::Locked)?;
+        state.slots.push(slot);
+        let n = state.slots.len();
+        self.touch();
+
+        Ok(n)
+    }
+
+    // Drops member slot n and moves the vault to a new data key, so the removed
+    // member cannot read later commits. Other members are wrapped again; password
+    // slots are dropped except the one `keep` opens, and any recovery slot is
+    // replaced by one for `recovery`
+    pub fn remove_member(
+        &mut self,
+        n: usize,
+        keep: Option<&Credentials>,
+        recovery: Option<&Credentials>,
+    ) -> Result<(), VaultError> {
+        let slots = self.key_slots()?.to_vec();
+        if n == 0 || slots.get(n - 1).is_none_or(|s| s.kind != SlotKind::Member) {
+            return Err(VaultError::KeySlotNotFound(n));
+        }
+
+        let version = migrations::FORMAT_VERSION;
+        let kept = match keep {
+            Some(creds) => {
+                let index = self.open_slot(version, &slots, SlotKey::Password(*creds))?;
+                Some((creds, slots[index].kdf))
+            }
+            None => None,
+        };
+        let recovery = match slots.iter().find(|s| s.kind == SlotKind::Recovery) {
+            Some(slot) => Some((recovery.ok_or(VaultError::NoRecoveryKey)?, slot.kdf)),
+            None => None,
+        };
+        let members: Vec<Member> = slots
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| *i != n - 1)
+            .filter_map(|(_, s)| s.member)
+            .collect();
+        if members.is_empty() && kept.is_none() {
+            return Err(VaultError::LastKeySlot);
+        }
+
+        // The current data key is escrowed to a throwaway identity in case re-wrapping fails
+        let (mut secret, public) = self.crypto.generate_identity();
+        let escrow = match self.crypto.wrap_key_for(&public) {
+            Ok(escrow) => escrow,
+            Err(e) => {
+                secret.zeroize();
+                return Err(e.into());
+            }
+        };
+
+        let new_slots = match self.reseal(|engine| engine.rewrap(kept, recovery, members)) {
+            Ok(slots) => slots,
+            Err(e) => {
+                let restored = self.crypto.unwrap_key_with(&escrow, &secret);
+                secret.zeroize();
+                restored?;
+                return Err(e);
+            }
+        };
+        secret.zeroize();
+
+        if let Some(state) = self.vault_state.as_mut() {
+            state.slots = new_slots;
+        }
+        self.touch();
+
+        Ok(())
+    }
+
+    // New data key wrapped for the kept password slot, the recovery key and the members
+    fn rewrap(
+        &mut self,
+        kept: Option<(&Credentials, KdfParams)>,
+        recovery: Option<(&Credentials, KdfParams)>,
+        members: Vec<Member>,
+    ) -> Result<Vec<KeySlot>, VaultError> {
+        self.crypto.generate_key()?;
+
+        let mut slots = Vec::new();
+        if let Some((creds, kdf)) = kept {
+            slots.push(self.new_slot(creds, kdf, SlotKind::Password)?);
+        }
+        if let Some((creds, kdf)) = recovery {
+            slots.push(self.new_slot(creds, kdf, SlotKind::Recovery)?);
+        }
+        for member in members {
+            slots.push(self.member_slot(member)?);
+        }
+        Ok(slots)
+    }
+
+    // Replaces any recovery slot with one for the new key and commits
+    pub fn rotate_recovery(&mut self, recovery: &Credentials) -> Result<(), VaultError> {
+        let backup = self.vault_state.clone().ok_or(VaultError::Locked)?;
+        let slot = self.new_slot(recovery, KdfParams::default(), SlotKind::Recovery)?;
+
+        if let Some(state) = self.vault_state.as_mut() {
+            state.slots.retain(|s| s.kind != SlotKind::Recovery);
+            state.slots.push(slot);
+        }
+
+        if let Err(e) = self.commit() {
+            self.vault_state = Some(backup);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    // Replaces the slot opened by `old` and commits, pending changes included.
+    // The previous file stays as backup and the old header is kept on failure.
+    fn rederive(
+        &mut self,
+        old: &Credentials,
+        new: &Credentials,
+        kdf: impl FnOnce(KdfParams) -> KdfParams,
+        suite: CipherSuite,
+        recovery: Option<&Credentials>,
+    ) -> Result<(), VaultError> {
+        let backup = self.vault_state.clone().ok_or(VaultError::Locked)?;
+        let entries = self.entries.clone();
+        let version = migrations::FORMAT_VERSION;
+        let index = self.open_slot(version, &backup.slots, SlotKey::Password(*old))?;
+        let kdf = kdf(backup.slots[index].kdf);
 
-        if self.entries.contains_key(service) {
+        let res = self
+            .replace_slot(index, new, kdf, suite, recovery)
+            .and_then(|_| self.commit());
+
+        if let Err(e) = res {
+            self.crypto.select(backup.suite)?;
+            self.open_slot(version, &backup.slots, SlotKey::Password(*old))?;
+            self.vault_state = Some(backup);
+            self.entries = entries;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    fn replace_slot(
+        &mut self,
+        index: usize,
+        creds: &Credentials,
+        kdf: KdfParams,
+        suite: CipherSuite,
+        recovery: Option<&Credentials>,
+    ) -> Result<(), VaultError> {
+        let suite_changed = suite != self.suite()?;
+        if !suite_changed {
+            let slot = self.new_slot(creds, kdf, SlotKind::Password)?;
+            let state = self.vault_state.as_mut().ok_or(VaultError::Locked)?;
+            state.slots[index] = slot;
+            return Ok(());
+        }
+
+        // Members only need their public keys to be wrapped again
+        let slots = self.key_slots()?;
+        let recovery = match slots.iter().find(|s| s.kind == SlotKind::Recovery) {
+            Some(slot) => Some((recovery.ok_or(VaultError::NoRecoveryKey)?, slot.kdf)),
+            None => None,
+        };
+        let members: Vec<Member> = slots.iter().filter_map(|s| s.member.clone()).collect();
+
+        let slots = self.reseal(|engine| {
+            engine.crypto.select(suite)?;
+            engine.rewrap(Some((creds, kdf)), recovery, members)
+        })?;
+
+        let state = self.vault_state.as_mut().ok_or(VaultError::Locked)?;
+        state.suite = suite;
+        state.slots = slots;
+
+        Ok(())
+    }
+
+    // Locks an idle session, keeping or dropping uncommitted changes per policy.
+    // It locks even when the commit fails, whose error is returned afterwards
+    pub fn auto_lock(&mut self, policy: IdlePolicy) -> Result<(), VaultError> {
+        let committed = if self.is_dirty() && policy == IdlePolicy::Commit {
+            self.commit()
+        } else {
+            Ok(())
+        };
+        self.lock()?;
+        committed
+    }
+
+    pub fn add(&mut self, service: &str, username: &str, password: &str) -> Result<EntryId, VaultError> {
+        if self.is_locked() {
+            return Err(VaultError::Locked);
+        }
+
+        if self.find_account(service, username).is_some() {
             return Err(VaultError::EntryExists);
         }
 
-        self.entries.insert(service.into(), entry);
+        // Ids are never derived from service or username, so renames keep them
+        let id = self.next_id;
+        let mut entry = Entry::new(id, service.into(), username.into(), password.into());
+        let sealed = self.seal(&entry);
+        entry.zeroize();
+
+        self.entries.insert(id, sealed?);
+        self.next_id = id + 1;
+        self.touch();
+
+        Ok(id)
+    }
+
+    pub fn update(&mut self, id: EntryId, update: &EntryUpdate) -> Result<(), VaultError> {
+        if self.is_locked() {
+            return Err(VaultError::Locked);
+        }
+
+        let current = self.get_index(id)?;
+
+        // Renaming must not collide with another account
+        let service = update.service.as_deref().unwrap_or(&current.service);
+        let username = update.username.as_deref().unwrap_or(&current.username);
+        if self.find_account(service, username).is_some_and(|other| other != id) {
+            return Err(VaultError::EntryExists);
+        }
+
+        if update.is_empty() {
+            return Ok(());
+        }
+
+        self.modify(id, |entry| {
+            if let Some(service) = &update.service {
+                entry.service = service.clone();
+            }
+            if let Some(username) = &update.username {
+                entry.username = username.clone();
+            }
+            if let Some(passwd) = &update.passwd {
+                entry.set_password(passwd.clone());
+            }
+            if let Some(urls) = &update.urls {
+                entry.urls = urls.clone();
+            }
+            if let Some(notes) = &update.notes {
+                entry.notes.zeroize();
+                entry.notes = notes.clone();
+            }
+            if let Some(questions) = &update.questions {
+                entry.questions.zeroize();
+                entry.questions = questions.clone();
+            }
+            if let Some(fields) = &update.fields {
+                entry.fields.zeroize();
+                entry.fields = fields.clone();
+            }
+            if let Some(totp) = &update.totp {
+                entry.totp.zeroize();
+                entry.totp = totp.clone();
+            }
+            if let Some(tags) = &update.tags {
+                entry.tags = tags.clone();
+            }
+            if let Some(folder) = &update.folder {
+                entry.folder = folder.as_deref().map(|f| f.trim_matches('/').to_string());
+            }
+            entry.touch();
+        })?;
+
+        self.touch();
+
+        Ok(())
+    }
+
+    // Current code and seconds until it rolls over
+    pub fn totp(&self, id: EntryId) -> Result<(String, u64), VaultError> {
+        let mut entry = self.get(id)?;
+        let now = chrono::Utc::now().timestamp() as u64;
+        let code = match &entry.totp {
+            Some(secret) => totp::generate(secret, now).map_err(Into::into),
+            None => Err(TotpError::NotConfigured.into()),
+        };
+        entry.zeroize();
+        code
+    }
+
+    // Decrypted copy, zeroize it when done
+    pub fn history(&self, id: EntryId) -> Result<Vec<PasswordRecord>, VaultError> {
+        let mut entry = self.get(id)?;
+        let history = entry.history().to_vec();
+        entry.zeroize();
+        Ok(history)
+    }
+
+    // `n` counts from 1, the most recent previous password
+    pub fn restore(&mut self, id: EntryId, n: usize) -> Result<(), VaultError> {
+        if self.is_locked() {
+            return Err(VaultError::Locked);
+        }
+
+        let restored = n > 0 && self.modify(id, |entry| entry.restore(n - 1))?;
+        if !restored {
+            return Err(VaultError::HistoryNotFound(n));
+        }
+        self.touch();
 
         Ok(())
     }
 
-    pub fn delete(&mut self, service: &str) -> Result<Entry, VaultError> {
+    pub fn delete(&mut self, id: EntryId) -> Result<EntryIndex, VaultError> {
+        if self.is_locked() {
+            return Err(VaultError::Locked);
+        }
+        let entry = self.entries.remove(&id).ok_or(VaultError::EntryNotFound)?;
+        self.touch();
+
+        Ok(entry.index)
+    }
+
+    // Decrypts the secrets of one entry, zeroize the copy when done
+    pub fn get(&self, id: EntryId) -> Result<Entry, VaultError> {
+        if self.is_locked() {
+            return Err(VaultError::Locked);
+        }
+        let sealed = self.entries.get(&id).ok_or(VaultError::EntryNotFound)?;
+        self.unseal(sealed)
+    }
+
+    // Service, username and the rest of the index, without decrypting anything
+    pub fn get_index(&self, id: EntryId) -> Result<&EntryIndex, VaultError> {
+        if self.is_locked() {
+            return Err(VaultError::Locked);
+        }
+        self.entries.get(&id).map(|e| &e.index).ok_or(VaultError::EntryNotFound)
+    }
+
+    // All accounts of a service, optionally narrowed to one username
+    pub fn find(
+        &self,
+        service: &str,
+        username: Option<&str>,
+    ) -> Result<Vec<&EntryIndex>, VaultError> {
         if self.is_locked() {
             return Err(VaultError::Locked);
         }
-        self.entries
-            .remove(service)
-            .ok_or(VaultError::EntryNotFound)
+
+        let accounts: Vec<&EntryIndex> = self
+            .indexes()
+            .filter(|e| e.service == service)
+            .filter(|e| username.is_none_or(|u| e.username == u))
+            .collect();
+
+        if accounts.is_empty() {
+            return Err(VaultError::EntryNotFound);
+        }
+        Ok(accounts)
+    }
+
+    // Resolves service (+ username) to a single entry
+    pub fn lookup(&self, service: &str, username: Option<&str>) -> Result<EntryId, VaultError> {
+        match self.find(service, username)?.as_slice() {
+            [entry] => Ok(entry.id),
+            _ => Err(VaultError::AmbiguousEntry(service.into())),
+        }
+    }
+
+    pub fn get_entries(&self) -> Result<Vec<&EntryIndex>, VaultError> {
+        self.filter_entries(&EntryFilter::default())
     }
 
-    pub fn get(&self, service: &str) -> Result<&Entry, VaultError> {
+    pub fn filter_entries(&self, filter: &EntryFilter) -> Result<Vec<&EntryIndex>, VaultError> {
         if self.is_locked() {
             return Err(VaultError::Locked);
         }
-        self.entries
-            .get(service)
-            .ok_or(VaultError::EntryNotFound)
+        let mut entries: Vec<&EntryIndex> = self.indexes().filter(|e| filter.matches(e)).collect();
+        entries.sort_by(|a, b| (&a.service, &a.username).cmp(&(&b.service, &b.username)));
+        Ok(entries)
     }
 
-    pub fn get_entries(&self) -> Result<Vec<String>, VaultError> {
+    // Entries matching `query` on service, username, URLs or tags, best first
+    pub fn search(&self, query: &str) -> Result<Vec<&EntryIndex>, VaultError> {
         if self.is_locked() {
             return Err(VaultError::Locked);
         }
-        Ok(self.entries.keys().cloned().collect())
+
+        let weighted = |weight: i64, field: &str| fuzzy_score(query, field).map(|s| s * weight);
+
+        let mut hits: Vec<(i64, &EntryIndex)> = self
+            .indexes()
+            .filter_map(|e| {
+                let score = std::iter::once(weighted(3, &e.service))
+                    .chain(std::iter::once(weighted(2, &e.username)))
+                    .chain(e.urls.iter().map(|u| weighted(1, u)))
+                    .chain(e.tags.iter().map(|t| weighted(2, t)))
+                    .flatten()
+                    .max()?;
+                Some((score, e))
+            })
+            .collect();
+
+        hits.sort_by(|(a, ea), (b, eb)| b.cmp(a).then_with(|| ea.service.cmp(&eb.service)));
+        Ok(hits.into_iter().map(|(_, e)| e).collect())
+    }
+
+    pub fn tags(&self) -> BTreeSet<String> {
+        self.indexes().flat_map(|e| e.tags.iter().cloned()).collect()
+    }
+
+    // Every folder in use, including intermediate parents
+    pub fn folders(&self) -> BTreeSet<String> {
+        let mut folders = BTreeSet::new();
+        for folder in self.indexes().filter_map(|e| e.folder.as_deref()) {
+            for (i, _) in folder.match_indices('/') {
+                folders.insert(folder[..i].to_string());
+            }
+            folders.insert(folder.to_string());
+        }
+        folders
+    }
+
+    fn find_account(&self, service: &str, username: &str) -> Option<EntryId> {
+        self.indexes()
+            .find(|e| e.service == service && e.username == username)
+            .map(|e| e.id)
+    }
+
+    fn indexes(&self) -> impl Iterator<Item = &EntryIndex> {
+        self.entries.values().map(|e| &e.index)
+    }
+
+    /* =======================
+       ENTRY SEALING
+    ======================= */
+
+    // Encrypts the secrets of an entry under its subkey
+    fn seal(&self, entry: &Entry) -> Result<SealedEntry, VaultError> {
+        let mut secrets = entry.secrets();
+        let encrypted = wincode::serialized_size(&secrets)
+            .map_err(|_| VaultError::Serialization)
+            .and_then(|size| {
+                let mut buffer = SecureBuffer::new(size as usize);
+                wincode::serialize_into(&mut &mut buffer[..], &secrets)
+                    .map_err(|_| VaultError::Serialization)?;
+                Ok(self.crypto.encrypt_entry(entry.id, &buffer)?)
+            });
+        secrets.zeroize();
+
+        let (cipher, nonce) = encrypted?;
+        Ok(SealedEntry {
+            index: entry.index(),
+            nonce,
+            cipher,
+        })
+    }
+
+    fn seal_all<'a>(
+        &self,
+        entries: impl IntoIterator<Item = &'a Entry>,
+    ) -> Result<BTreeMap<EntryId, SealedEntry>, VaultError> {
+        entries
+            .into_iter()
+            .map(|entry| Ok((entry.id, self.seal(entry)?)))
+            .collect()
+    }
+
+    fn unseal(&self, sealed: &SealedEntry) -> Result<Entry, VaultError> {
+        let plain = self
+            .crypto
+            .decrypt_entry(sealed.index.id, &sealed.cipher, &sealed.nonce)?;
+        let secrets =
+            wincode::deserialize_from(&mut &plain[..]).map_err(|_| VaultError::Serialization)?;
+
+        Ok(Entry::from_parts(sealed.index.clone(), secrets))
+    }
+
+    // Opens one entry, applies `change` and seals it again
+    fn modify<T>(
+        &mut self,
+        id: EntryId,
+        change: impl FnOnce(&mut Entry) -> T,
+    ) -> Result<T, VaultError> {
+        let mut entry = self.get(id)?;
+        let res = change(&mut entry);
+        let sealed = self.seal(&entry);
+        entry.zeroize();
+
+        self.entries.insert(id, sealed?);
+        Ok(res)
+    }
+
+    // Moves every entry to the data key `change` installs. Entries are left
+    // untouched on failure, restoring the previous key is up to the caller
+    fn reseal<T>(
+        &mut self,
+        change: impl FnOnce(&mut Self) -> Result<T, VaultError>,
+    ) -> Result<T, VaultError> {
+        let mut entries: Vec<Entry> = self
+            .entries
+            .values()
+            .map(|sealed| self.unseal(sealed))
+            .collect::<Result<_, _>>()?;
+
+        let res = change(self).and_then(|t| Ok((t, self.seal_all(&entries)?)));
+        entries.zeroize();
+
+        let (t, sealed) = res?;
+        self.entries = sealed;
+        Ok(t)
     }
 
     pub fn get_vaults(&self) -> Result<Vec<String>, VaultError> {
@@ -172,3 +1014,118 @@ impl<S: StoragePort, C: CryptoPort> VaultEngine<S, C> {
         Ok(vaults)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adapters::{memory_storage::MemoryStorage, recipient, suite_crypto::SuiteCrypto},
+        domain::models::KdfAlgorithm,
+    };
+
+    // Cheapest parameters Argon2 accepts
+    const FAST_KDF: KdfParams = KdfParams {
+        algorithm: KdfAlgorithm::Argon2id,
+        memory_kib: 8,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    fn creds() -> Credentials<'static> {
+        Credentials::new("correct horse battery staple", None)
+    }
+
+    fn new_vault(storage: &MemoryStorage) -> VaultEngine<MemoryStorage, SuiteCrypto> {
+        let mut engine = VaultEngine::new(storage.clone(), SuiteCrypto::new());
+        engine
+            .create_vault("test", &creds(), FAST_KDF, CipherSuite::default(), None)
+            .unwrap();
+        engine
+    }
+
+    #[test]
+    fn deleted_entry_ids_are_never_reused() {
+        let storage = MemoryStorage::default();
+        let mut engine = new_vault(&storage);
+
+        let first = engine.add("github", "alice", "hunter2").unwrap();
+        let second = engine.add("mail", "bob", "swordfish").unwrap();
+        engine.delete(second).unwrap();
+        let third = engine.add("bank", "carol", "letmein").unwrap();
+        assert!(third > second && second > first);
+
+        // The next id survives a commit even when the highest entry is gone
+        engine.delete(third).unwrap();
+        engine.commit().unwrap();
+        engine.lock().unwrap();
+        engine.unlock("test", &creds()).unwrap();
+        assert!(engine.add("shop", "dave", "qwerty").unwrap() > third);
+    }
+
+    #[test]
+    fn create_refuses_existing_vault() {
+        let storage = MemoryStorage::default();
+        let mut engine = new_vault(&storage);
+        engine.add("github", "alice", "hunter2").unwrap();
+        engine.commit().unwrap();
+        engine.lock().unwrap();
+
+        let res = engine.create_vault("test", &creds(), FAST_KDF, CipherSuite::default(), None);
+        assert!(matches!(res, Err(VaultError::VaultExists)));
+        engine.unlock("test", &creds()).unwrap();
+        assert_eq!(engine.get_entries().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn cipher_change_replaces_recovery_slot() {
+        let storage = MemoryStorage::default();
+        let mut engine = VaultEngine::new(storage, SuiteCrypto::new());
+        let old = Credentials::new("old recovery", None);
+        let new = Credentials::new("new recovery", None);
+        engine
+            .create_vault("test", &creds(), FAST_KDF, CipherSuite::Aes256Gcm, Some(&old))
+            .unwrap();
+        engine.commit().unwrap();
+
+        // The recovery slot cannot be wrapped for the new data key without a new key
+        let suite = CipherSuite::XChaCha20Poly1305;
+        let res = engine.rekey(&creds(), |kdf| kdf, suite, None);
+        assert!(matches!(res, Err(VaultError::NoRecoveryKey)));
+        engine.rekey(&creds(), |kdf| kdf, suite, Some(&new)).unwrap();
+        engine.lock().unwrap();
+
+        let res = engine.unlock_recovery("test", &old);
+        assert!(matches!(res, Err(VaultError::InvalidPassword)));
+        engine.unlock_recovery("test", &new).unwrap();
+        assert_eq!(engine.suite().unwrap(), suite);
+    }
+
+    #[test]
+    fn removed_member_cannot_open_later_commits() {
+        let storage = MemoryStorage::default();
+        let mut engine = new_vault(&storage);
+        let id = engine.add("github", "alice", "hunter2").unwrap();
+        let (removed, removed_public) = recipient::generate_identity();
+        let (kept, kept_public) = recipient::generate_identity();
+        assert_eq!(engine.add_member("removed", removed_public).unwrap(), 2);
+        assert_eq!(engine.add_member("kept", kept_public).unwrap(), 3);
+        engine.commit().unwrap();
+
+        engine.remove_member(2, Some(&creds()), None).unwrap();
+        engine.commit().unwrap();
+        engine.lock().unwrap();
+
+        let res = engine.unlock_identity("test", &removed);
+        assert!(matches!(res, Err(VaultError::NotAMember)));
+        assert!(engine.is_locked());
+
+        engine.unlock_identity("test", &kept).unwrap();
+        let mut entry = engine.get(id).unwrap();
+        assert_eq!(entry.passwd, "hunter2");
+        entry.zeroize();
+        engine.lock().unwrap();
+
+        engine.unlock("test", &creds()).unwrap();
+        assert_eq!(engine.key_slots().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,644 @@
+use std::collections::BTreeMap;
+
+use wincode::{SchemaRead, SchemaWrite};
+
+use crate::domain::{
+    errors::VaultError,
+    models::{CipherSuite, Entry, EntryId, KdfParams, KeySlot, SealedEntry, SlotKind, VaultState},
+};
+
+/* =======================
+   FILE HEADER
+======================= */
+pub const MAGIC: [u8; 4] = *b"PVLT";
+
+// Bump together with a new arm in `read_state`/`read_entries`/`read_index`
+pub const FORMAT_VERSION: u16 = 9;
+
+// First version whose header is authenticated as associated data
+pub const AAD_VERSION: u16 = 2;
+
+// First version recording the cipher suite
+pub const SUITE_VERSION: u16 = 3;
+
+// First version recording whether a key file is required
+pub const KEYFILE_VERSION: u16 = 4;
+
+// First version with a random data key wrapped by key slots
+pub const ENVELOPE_VERSION: u16 = 5;
+
+// First version recording the kind of each key slot
+pub const SLOT_KIND_VERSION: u16 = 6;
+
+// First version with member slots wrapped for public keys
+pub const MEMBER_VERSION: u16 = 7;
+
+// First version sealing the secrets of each entry under its own subkey
+pub const SEALED_VERSION: u16 = 8;
+
+// First version recording the next entry id, so deleted ids are never handed out again
+pub const NEXT_ID_VERSION: u16 = 9;
+
+pub fn write_header(out: &mut Vec<u8>) {
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+}
+
+// Files without the magic bytes predate versioning and are version 0
+pub fn split_header(bytes: &[u8]) -> Result<(u16, &[u8]), VaultError> {
+    let Some(rest) = bytes.strip_prefix(&MAGIC) else {
+        return Ok((0, bytes));
+    };
+
+    let (version, body) = rest
+        .split_first_chunk::<2>()
+        .ok_or(VaultError::Serialization)?;
+    let version = u16::from_le_bytes(*version);
+
+    if version > FORMAT_VERSION {
+        return Err(VaultError::UnsupportedVersion {
+            found: version,
+            supported: FORMAT_VERSION,
+        });
+    }
+    Ok((version, body))
+}
+
+// Magic, version, cipher suite and key slots, bound to the ciphertext.
+// Before v5 the single slot contributed its KDF parameters, salt and key file flag
+pub fn header_aad(version: u16, state: &VaultState) -> Result<Vec<u8>, VaultError> {
+    let mut aad = Vec::new();
+    aad.extend_from_slice(&MAGIC);
+    aad.extend_from_slice(&version.to_le_bytes());
+    if version >= SUITE_VERSION {
+        wincode::serialize_into(&mut aad, &state.suite).map_err(|_| VaultError::Serialization)?;
+    }
+
+    if version >= MEMBER_VERSION {
+        wincode::serialize_into(&mut aad, &state.slots).map_err(|_| VaultError::Serialization)?;
+        return Ok(aad);
+    }
+    if version >= SLOT_KIND_VERSION {
+        let slots: Vec<KeySlotV6> = state.slots.iter().map(KeySlotV6::from).collect();
+        wincode::serialize_into(&mut aad, &slots).map_err(|_| VaultError::Serialization)?;
+        return Ok(aad);
+    }
+    if version >= ENVELOPE_VERSION {
+        let slots: Vec<KeySlotV5> = state.slots.iter().map(KeySlotV5::from).collect();
+        wincode::serialize_into(&mut aad, &slots).map_err(|_| VaultError::Serialization)?;
+        return Ok(aad);
+    }
+
+    let slot = state.slots.first().ok_or(VaultError::Serialization)?;
+    wincode::serialize_into(&mut aad, &slot.kdf).map_err(|_| VaultError::Serialization)?;
+    aad.extend_from_slice(&slot.salt);
+    if version >= KEYFILE_VERSION {
+        aad.push(slot.keyfile as u8);
+    }
+    Ok(aad)
+}
+
+/* =======================
+   LEGACY LAYOUTS
+======================= */
+
+// v0: raw wincode, no KDF parameters, entries keyed by service
+#[derive(SchemaRead)]
+#[cfg_attr(test, derive(SchemaWrite))]
+struct VaultStateV0 {
+    salt: [u8; 16],
+    nonce: [u8; 12],
+    cipher: Vec<u8>,
+}
+
+// v1: KDF parameters, no key check
+#[derive(SchemaRead)]
+#[cfg_attr(test, derive(SchemaWrite))]
+struct VaultStateV1 {
+    kdf: KdfParams,
+    salt: [u8; 16],
+    nonce: [u8; 12],
+    cipher: Vec<u8>,
+}
+
+// v2: key check, header authenticated, AES-256-GCM only
+#[derive(SchemaRead)]
+#[cfg_attr(test, derive(SchemaWrite))]
+struct VaultStateV2 {
+    kdf: KdfParams,
+    salt: [u8; 16],
+    key_check: Vec<u8>,
+    nonce: [u8; 12],
+    cipher: Vec<u8>,
+}
+
+// v3: cipher suite, no key file support
+#[derive(SchemaRead)]
+#[cfg_attr(test, derive(SchemaWrite))]
+struct VaultStateV3 {
+    suite: CipherSuite,
+    kdf: KdfParams,
+    salt: [u8; 16],
+    key_check: Vec<u8>,
+    nonce: Vec<u8>,
+    cipher: Vec<u8>,
+}
+
+// v4: key file flag, key derived straight from the credentials
+#[derive(SchemaRead)]
+#[cfg_attr(test, derive(SchemaWrite))]
+struct VaultStateV4 {
+    suite: CipherSuite,
+    kdf: KdfParams,
+    salt: [u8; 16],
+    keyfile: bool,
+    key_check: Vec<u8>,
+    nonce: Vec<u8>,
+    cipher: Vec<u8>,
+}
+
+// v5: key slots without a kind, all of them passwords
+#[derive(SchemaRead)]
+#[cfg_attr(test, derive(SchemaWrite))]
+struct VaultStateV5 {
+    suite: CipherSuite,
+    slots: Vec<KeySlotV5>,
+    nonce: Vec<u8>,
+    cipher: Vec<u8>,
+}
+
+#[derive(SchemaRead, SchemaWrite)]
+struct KeySlotV5 {
+    kdf: KdfParams,
+    salt: [u8; 16],
+    keyfile: bool,
+    wrapped: Vec<u8>,
+}
+
+// v5 headers are authenticated in their own slot layout
+impl From<&KeySlot> for KeySlotV5 {
+    fn from(slot: &KeySlot) -> Self {
+        Self {
+            kdf: slot.kdf,
+            salt: slot.salt,
+            keyfile: slot.keyfile,
+            wrapped: slot.wrapped.clone(),
+        }
+    }
+}
+
+// v6: slot kinds, no member slots
+#[derive(SchemaRead)]
+#[cfg_attr(test, derive(SchemaWrite))]
+struct VaultStateV6 {
+    suite: CipherSuite,
+    slots: Vec<KeySlotV6>,
+    nonce: Vec<u8>,
+    cipher: Vec<u8>,
+}
+
+#[derive(SchemaRead, SchemaWrite)]
+struct KeySlotV6 {
+    kind: SlotKind,
+    kdf: KdfParams,
+    salt: [u8; 16],
+    keyfile: bool,
+    wrapped: Vec<u8>,
+}
+
+impl From<&KeySlot> for KeySlotV6 {
+    fn from(slot: &KeySlot) -> Self {
+        Self {
+            kind: slot.kind,
+            kdf: slot.kdf,
+            salt: slot.salt,
+            keyfile: slot.keyfile,
+            wrapped: slot.wrapped.clone(),
+        }
+    }
+}
+
+#[derive(SchemaRead)]
+#[cfg_attr(test, derive(SchemaWrite))]
+struct EntryV0 {
+    service: String,
+    username: String,
+    passwd: String,
+    created_at: i64,
+    updated_at: i64,
+}
+
+/* =======================
+   MIGRATIONS
+======================= */
+
+// Decodes the header of any supported version into the current layout
+pub fn read_state(version: u16, mut body: &[u8]) -> Result<VaultState, VaultError> {
+    match version {
+        0 => {
+            let v0: VaultStateV0 =
+                wincode::deserialize_from(&mut body).map_err(|_| VaultError::Serialization)?;
+            // v0 vaults were derived with argon2's defaults
+            let slot = legacy_slot(KdfParams::default(), v0.salt, false, vec![]);
+            Ok(legacy_state(CipherSuite::Aes256Gcm, slot, v0.nonce.to_vec(), v0.cipher))
+        }
+        1 => {
+            let v1: VaultStateV1 =
+                wincode::deserialize_from(&mut body).map_err(|_| VaultError::Serialization)?;
+            let slot = legacy_slot(v1.kdf, v1.salt, false, vec![]);
+            Ok(legacy_state(CipherSuite::Aes256Gcm, slot, v1.nonce.to_vec(), v1.cipher))
+        }
+        2 => {
+            let v2: VaultStateV2 =
+                wincode::deserialize_from(&mut body).map_err(|_| VaultError::Serialization)?;
+            let slot = legacy_slot(v2.kdf, v2.salt, false, v2.key_check);
+            Ok(legacy_state(CipherSuite::Aes256Gcm, slot, v2.nonce.to_vec(), v2.cipher))
+        }
+        3 => {
+            let v3: VaultStateV3 =
+                wincode::deserialize_from(&mut body).map_err(|_| VaultError::Serialization)?;
+            let slot = legacy_slot(v3.kdf, v3.salt, false, v3.key_check);
+            Ok(legacy_state(v3.suite, slot, v3.nonce, v3.cipher))
+        }
+        4 => {
+            let v4: VaultStateV4 =
+                wincode::deserialize_from(&mut body).map_err(|_| VaultError::Serialization)?;
+            let slot = legacy_slot(v4.kdf, v4.salt, v4.keyfile, v4.key_check);
+            Ok(legacy_state(v4.suite, slot, v4.nonce, v4.cipher))
+        }
+        5 => {
+            let v5: VaultStateV5 =
+                wincode::deserialize_from(&mut body).map_err(|_| VaultError::Serialization)?;
+            let slots = v5
+                .slots
+                .into_iter()
+                .map(|s| legacy_slot(s.kdf, s.salt, s.keyfile, s.wrapped))
+                .collect();
+            Ok(VaultState {
+                suite: v5.suite,
+                slots,
+                nonce: v5.nonce,
+                cipher: v5.cipher,
+            })
+        }
+        6 => {
+            let v6: VaultStateV6 =
+                wincode::deserialize_from(&mut body).map_err(|_| VaultError::Serialization)?;
+            let slots = v6
+                .slots
+                .into_iter()
+                .map(|s| KeySlot {
+                    kind: s.kind,
+                    kdf: s.kdf,
+                    salt: s.salt,
+                    keyfile: s.keyfile,
+                    member: None,
+                    wrapped: s.wrapped,
+                })
+                .collect();
+            Ok(VaultState {
+                suite: v6.suite,
+                slots,
+                nonce: v6.nonce,
+                cipher: v6.cipher,
+            })
+        }
+        _ => wincode::deserialize_from(&mut body).map_err(|_| VaultError::Serialization),
+    }
+}
+
+// Slots before v6 are all password slots. Pre-envelope vaults keep their key
+// parameters and key check in a single one
+fn legacy_slot(kdf: KdfParams, salt: [u8; 16], keyfile: bool, wrapped: Vec<u8>) -> KeySlot {
+    KeySlot {
+        kind: SlotKind::Password,
+        kdf,
+        salt,
+        keyfile,
+        member: None,
+        wrapped,
+    }
+}
+
+fn legacy_state(suite: CipherSuite, slot: KeySlot, nonce: Vec<u8>, cipher: Vec<u8>) -> VaultState {
+    VaultState {
+        suite,
+        slots: vec![slot],
+        nonce,
+        cipher,
+    }
+}
+
+// Decodes decrypted entries of a vault before v8 into the current layout
+pub fn read_entries(
+    version: u16,
+    mut plaintext: &[u8],
+) -> Result<BTreeMap<EntryId, Entry>, VaultError> {
+    match version {
+        0 => {
+            let v0: BTreeMap<String, EntryV0> =
+                wincode::deserialize_from(&mut plaintext).map_err(|_| VaultError::Serialization)?;
+
+            // Ids follow the old service order
+            Ok((1..)
+                .zip(v0.into_values())
+                .map(|(id, e)| {
+                    let entry = Entry::migrated(
+                        id,
+                        e.service,
+                        e.username,
+                        e.passwd,
+                        e.created_at,
+                        e.updated_at,
+                    );
+                    (id, entry)
+                })
+                .collect())
+        }
+        _ => wincode::deserialize_from(&mut plaintext).map_err(|_| VaultError::Serialization),
+    }
+}
+
+// Decodes the decrypted index of a vault since v8 as the next entry id and the
+// entries, whose secrets stay sealed
+pub fn read_index(
+    version: u16,
+    mut plaintext: &[u8],
+) -> Result<(EntryId, BTreeMap<EntryId, SealedEntry>), VaultError> {
+    let next_id = if version >= NEXT_ID_VERSION {
+        Some(wincode::deserialize_from(&mut plaintext).map_err(|_| VaultError::Serialization)?)
+    } else {
+        None
+    };
+    let entries: BTreeMap<EntryId, SealedEntry> =
+        wincode::deserialize_from(&mut plaintext).map_err(|_| VaultError::Serialization)?;
+
+    let next_id = next_id.unwrap_or_else(|| next_free_id(&entries));
+    Ok((next_id, entries))
+}
+
+// Older vaults did not record ids of deleted entries, continue after the highest one
+pub fn next_free_id<T>(entries: &BTreeMap<EntryId, T>) -> EntryId {
+    entries.keys().next_back().map_or(1, |last| last + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use zeroize::Zeroize;
+
+    use super::*;
+    use crate::{
+        adapters::{memory_storage::MemoryStorage, suite_crypto::SuiteCrypto},
+        application::engine::VaultEngine,
+        domain::{
+            models::{Credentials, KdfAlgorithm, SealedEntry},
+            ports::{CryptoPort, StoragePort},
+        },
+    };
+
+    const PASSWORD: &str = "correct horse battery staple";
+    const KEY_CHECK: &[u8] = b"private-vault-key-check";
+
+    // Cheapest parameters Argon2 accepts, v0 vaults always used the defaults
+    const FAST_KDF: KdfParams = KdfParams {
+        algorithm: KdfAlgorithm::Argon2id,
+        memory_kib: 8,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    const PASSWORDS: [(EntryId, &str); 2] = [(1, "hunter2"), (2, "swordfish")];
+
+    fn creds() -> Credentials<'static> {
+        Credentials::new(PASSWORD, None)
+    }
+
+    fn suite(version: u16) -> CipherSuite {
+        if version >= SUITE_VERSION {
+            CipherSuite::XChaCha20Poly1305
+        } else {
+            CipherSuite::Aes256Gcm
+        }
+    }
+
+    fn serialize<T: SchemaWrite<Src = T>>(value: &T) -> Vec<u8> {
+        wincode::serialize(value).unwrap()
+    }
+
+    // Entries as each version encrypted them, ids in service order
+    fn plaintext(version: u16, crypto: &SuiteCrypto) -> Vec<u8> {
+        let services = [("github", "alice"), ("mail", "bob")];
+        if version == 0 {
+            let entries: BTreeMap<String, EntryV0> = services
+                .iter()
+                .zip(PASSWORDS)
+                .map(|((service, username), (_, passwd))| {
+                    let entry = EntryV0 {
+                        service: service.to_string(),
+                        username: username.to_string(),
+                        passwd: passwd.into(),
+                        created_at: 0,
+                        updated_at: 0,
+                    };
+                    (service.to_string(), entry)
+                })
+                .collect();
+            return serialize(&entries);
+        }
+
+        let entries: BTreeMap<EntryId, Entry> = services
+            .iter()
+            .zip(PASSWORDS)
+            .map(|((service, username), (id, passwd))| {
+                (id, Entry::new(id, service.to_string(), username.to_string(), passwd.into()))
+            })
+            .collect();
+        if version < SEALED_VERSION {
+            return serialize(&entries);
+        }
+
+        // v8 seals each entry's secrets under its subkey, without a next id
+        let sealed: BTreeMap<EntryId, SealedEntry> = entries
+            .iter()
+            .map(|(id, entry)| {
+                let secrets = serialize(&entry.secrets());
+                let (cipher, nonce) = crypto.encrypt_entry(*id, &secrets).unwrap();
+                let index = entry.index();
+                (*id, SealedEntry { index, nonce, cipher })
+            })
+            .collect();
+        serialize(&sealed)
+    }
+
+    // Writes a vault the way the given format version did
+    fn legacy_file(version: u16) -> Vec<u8> {
+        let suite = suite(version);
+        let kdf = if version == 0 { KdfParams::default() } else { FAST_KDF };
+        let salt = [7u8; 16];
+        let mut crypto = SuiteCrypto::new();
+        crypto.select(suite).unwrap();
+
+        // Pre-envelope vaults use the derived key and keep a key check in its slot
+        let wrapped = if version >= ENVELOPE_VERSION {
+            crypto.generate_key().unwrap();
+            crypto.wrap_key(&creds(), &salt, &kdf).unwrap()
+        } else {
+            crypto.init(&creds(), &salt, &kdf).unwrap();
+            if version >= AAD_VERSION {
+                let (check, nonce) = crypto.encrypt(KEY_CHECK, &[]).unwrap();
+                [nonce, check].concat()
+            } else {
+                vec![]
+            }
+        };
+
+        let mut state = legacy_state(suite, legacy_slot(kdf, salt, false, wrapped), vec![], vec![]);
+        let aad = if version >= AAD_VERSION {
+            header_aad(version, &state).unwrap()
+        } else {
+            vec![]
+        };
+        let (cipher, nonce) = crypto.encrypt(&plaintext(version, &crypto), &aad).unwrap();
+        state.nonce = nonce;
+        state.cipher = cipher;
+
+        let slot = state.slots[0].clone();
+        let nonce = || <[u8; 12]>::try_from(state.nonce.clone()).unwrap();
+        let body = match version {
+            0 => serialize(&VaultStateV0 {
+                salt,
+                nonce: nonce(),
+                cipher: state.cipher.clone(),
+            }),
+            1 => serialize(&VaultStateV1 {
+                kdf,
+                salt,
+                nonce: nonce(),
+                cipher: state.cipher.clone(),
+            }),
+            2 => serialize(&VaultStateV2 {
+                kdf,
+                salt,
+                key_check: slot.wrapped,
+                nonce: nonce(),
+                cipher: state.cipher.clone(),
+            }),
+            3 => serialize(&VaultStateV3 {
+                suite,
+                kdf,
+                salt,
+                key_check: slot.wrapped,
+                nonce: state.nonce.clone(),
+                cipher: state.cipher.clone(),
+            }),
+            4 => serialize(&VaultStateV4 {
+                suite,
+                kdf,
+                salt,
+                keyfile: false,
+                key_check: slot.wrapped,
+                nonce: state.nonce.clone(),
+                cipher: state.cipher.clone(),
+            }),
+            5 => serialize(&VaultStateV5 {
+                suite,
+                slots: vec![KeySlotV5::from(&slot)],
+                nonce: state.nonce.clone(),
+                cipher: state.cipher.clone(),
+            }),
+            6 => serialize(&VaultStateV6 {
+                suite,
+                slots: vec![KeySlotV6::from(&slot)],
+                nonce: state.nonce.clone(),
+                cipher: state.cipher.clone(),
+            }),
+            _ => serialize(&state),
+        };
+
+        // v0 files have no header at all
+        if version == 0 {
+            return body;
+        }
+        let mut out = Vec::new();
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&version.to_le_bytes());
+        out.extend_from_slice(&body);
+        out
+    }
+
+    fn assert_passwords(engine: &VaultEngine<MemoryStorage, SuiteCrypto>, version: u16) {
+        for (id, expected) in PASSWORDS {
+            let mut entry = engine.get(id).unwrap();
+            assert_eq!(entry.passwd, expected, "v{}", version);
+            entry.zeroize();
+        }
+    }
+
+    #[test]
+    fn legacy_vaults_upgrade_on_commit() {
+        for version in 0..FORMAT_VERSION {
+            let storage = MemoryStorage::with_file(legacy_file(version));
+            let mut engine = VaultEngine::new(storage.clone(), SuiteCrypto::new());
+
+            engine
+                .unlock("legacy", &creds())
+                .unwrap_or_else(|e| panic!("v{}: {}", version, e));
+            assert_eq!(engine.migrated_from(), Some(version));
+            assert!(engine.is_dirty());
+            assert_passwords(&engine, version);
+
+            engine.commit().unwrap();
+            engine.lock().unwrap();
+            assert_eq!(split_header(&storage.bytes()).unwrap().0, FORMAT_VERSION);
+
+            engine
+                .unlock("legacy", &creds())
+                .unwrap_or_else(|e| panic!("v{} after commit: {}", version, e));
+            assert_eq!(engine.migrated_from(), None);
+            assert_eq!(engine.suite().unwrap(), suite(version));
+            assert_passwords(&engine, version);
+        }
+    }
+
+    #[test]
+    fn legacy_vaults_reject_wrong_password() {
+        for version in 0..FORMAT_VERSION {
+            let storage = MemoryStorage::with_file(legacy_file(version));
+            let mut engine = VaultEngine::new(storage, SuiteCrypto::new());
+
+            let res = engine.unlock("legacy", &Credentials::new("wrong", None));
+            assert!(matches!(res, Err(VaultError::InvalidPassword)), "v{}", version);
+            assert!(engine.is_locked());
+        }
+    }
+
+    #[test]
+    fn tampered_header_is_detected() {
+        let storage = MemoryStorage::default();
+        let mut engine = VaultEngine::new(storage.clone(), SuiteCrypto::new());
+        engine
+            .create_vault("tampered", &creds(), FAST_KDF, CipherSuite::default(), None)
+            .unwrap();
+        engine.add_slot(&Credentials::new("second", None), FAST_KDF).unwrap();
+        engine.commit().unwrap();
+        engine.lock().unwrap();
+
+        // The first slot still opens, but the second one no longer matches the AAD
+        let bytes = storage.bytes();
+        let (version, body) = split_header(&bytes).unwrap();
+        let mut state: VaultState = wincode::deserialize(body).unwrap();
+        state.slots[1].salt[0] ^= 1;
+        let mut tampered = Vec::new();
+        write_header(&mut tampered);
+        tampered.extend_from_slice(&serialize(&state));
+        assert_eq!(version, FORMAT_VERSION);
+        storage.save(&tampered).unwrap();
+
+        let res = engine.unlock("tampered", &creds());
+        assert!(matches!(res, Err(VaultError::HeaderTampered)));
+        assert!(engine.is_locked());
+
+        storage.save(&bytes).unwrap();
+        engine.unlock("tampered", &creds()).unwrap();
+    }
+}
@@ -1 +1,4 @@
 pub mod engine;
+pub mod migrations;
+pub mod search;
+pub mod totp;
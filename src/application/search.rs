@@ -0,0 +1,85 @@
+/* =======================
+   FUZZY MATCHING
+======================= */
+const EXACT_BONUS: i64 = 1000;
+const PREFIX_BONUS: i64 = 500;
+const SUBSTRING_BONUS: i64 = 250;
+const CONSECUTIVE_BONUS: i64 = 15;
+const WORD_START_BONUS: i64 = 10;
+const SUBSEQUENCE_BASE: i64 = 100;
+const GAP_PENALTY: i64 = 1;
+
+// Case-insensitive score of `query` against `candidate`, None if the
+// query is not a subsequence of the candidate
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let query = query.to_lowercase();
+    let candidate = candidate.to_lowercase();
+
+    if query.is_empty() {
+        return None;
+    }
+    if candidate == query {
+        return Some(EXACT_BONUS);
+    }
+    // Shorter candidates rank first, but length never drops a match to a lower tier
+    if candidate.starts_with(&query) {
+        return Some(length_penalty(PREFIX_BONUS, SUBSTRING_BONUS, &candidate));
+    }
+    if candidate.contains(&query) {
+        return Some(length_penalty(SUBSTRING_BONUS, SUBSTRING_BONUS / 2 + 1, &candidate));
+    }
+
+    // Subsequence match, rewarding runs and word starts
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut score = SUBSEQUENCE_BASE;
+    let mut pos = 0;
+    let mut last: Option<usize> = None;
+
+    for q in query.chars() {
+        let found = (pos..chars.len()).find(|&i| chars[i] == q)?;
+
+        if last.is_some_and(|l| l + 1 == found) {
+            score += CONSECUTIVE_BONUS;
+        } else {
+            score -= GAP_PENALTY * (found - pos) as i64;
+        }
+        if found == 0 || !chars[found - 1].is_alphanumeric() {
+            score += WORD_START_BONUS;
+        }
+
+        last = Some(found);
+        pos = found + 1;
+    }
+
+    // Always below a substring match, never zero
+    Some(score.clamp(1, SUBSTRING_BONUS / 2))
+}
+
+// `bonus` minus the candidate length, no lower than `floor`
+fn length_penalty(bonus: i64, floor: i64, candidate: &str) -> i64 {
+    let len = i64::try_from(candidate.len()).unwrap_or(i64::MAX);
+    bonus.saturating_sub(len).max(floor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_candidates_keep_their_tier() {
+        let long = "x".repeat(600);
+        let prefix = fuzzy_score("git", &format!("git{}", long)).unwrap();
+        let substring = fuzzy_score("git", &format!("{}git", long)).unwrap();
+        let subsequence = fuzzy_score("git", "g-i-t").unwrap();
+
+        assert!(prefix > substring, "{} > {}", prefix, substring);
+        assert!(substring > subsequence, "{} > {}", substring, subsequence);
+        assert!(subsequence > 0);
+    }
+
+    #[test]
+    fn shorter_candidates_rank_first() {
+        assert!(fuzzy_score("git", "github").unwrap() > fuzzy_score("git", "gitlab-ci").unwrap());
+        assert!(fuzzy_score("hub", "github").unwrap() > fuzzy_score("hub", "dockerhub.io").unwrap());
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::domain::models::CipherSuite;
+
 #[derive(Debug, Error)]
 pub enum VaultError {
     #[error("Vault is locked")]
@@ -14,20 +16,56 @@ pub enum VaultError {
     #[error("Entry not found")]
     EntryNotFound,
 
+    #[error("Several accounts found for '{0}', specify the username")]
+    AmbiguousEntry(String),
+
+    #[error("No password history record {0}")]
+    HistoryNotFound(usize),
+
     #[error("Vault not found")]
     VaultNotFound,
 
+    #[error("Vault already exists")]
+    VaultExists,
+
     #[error("Serialization failed")]
     Serialization,
 
     #[error("Invalid password or corrupted vault")]
     InvalidPassword,
 
+    #[error("Vault requires a key file")]
+    KeyfileRequired,
+
+    #[error("Key slot {0} not found")]
+    KeySlotNotFound(usize),
+
+    #[error("Cannot remove the last key slot")]
+    LastKeySlot,
+
+    #[error("Vault has no recovery key")]
+    NoRecoveryKey,
+
+    #[error("Identity is not a member of this vault")]
+    NotAMember,
+
+    #[error("Key slot {0} belongs to a member, remove it with 'member rm' to re-key the vault")]
+    MemberSlot(usize),
+
+    #[error("Vault header was modified or corrupted")]
+    HeaderTampered,
+
+    #[error("Vault format v{found} is newer than the supported v{supported}, please upgrade")]
+    UnsupportedVersion { found: u16, supported: u16 },
+
     #[error("Cryptography error: {0}")]
     Crypto(#[from] CryptoError),
 
     #[error("Storage error: {0}")]
     Storage(#[from] StorageError),
+
+    #[error("TOTP error: {0}")]
+    Totp(#[from] TotpError),
 }
 
 #[derive(Debug, Error)]
@@ -50,6 +88,66 @@ pub enum CryptoError {
     #[error("Aead error: {0}")]
     Aead(String),
 
+    #[error("Invalid data key")]
+    InvalidKey,
+
+    #[error("Invalid public key")]
+    InvalidPublicKey,
+
     #[error("Error while derivating key")]
     KeyDerivationError,
+
+    #[error("Invalid KDF parameters")]
+    InvalidKdfParams,
+
+    #[error("Cipher suite {0} not supported")]
+    UnsupportedSuite(CipherSuite),
+}
+
+#[derive(Debug, Error)]
+pub enum GeneratorError {
+    #[error("At least one character class must be enabled")]
+    NoCharacterClass,
+
+    #[error("Length must be at least {0}")]
+    TooShort(usize),
+}
+
+#[derive(Debug, Error)]
+pub enum RecoveryKeyError {
+    #[error("Recovery key must have {expected} words, got {found}")]
+    Length { expected: usize, found: usize },
+
+    #[error("Unknown word '{0}' in recovery key")]
+    UnknownWord(String),
+
+    #[error("Recovery key checksum mismatch")]
+    Checksum,
+}
+
+#[derive(Debug, Error)]
+pub enum TotpError {
+    #[error("No TOTP secret configured")]
+    NotConfigured,
+
+    #[error("Invalid otpauth URI")]
+    InvalidUri,
+
+    #[error("Invalid base32 secret")]
+    InvalidSecret,
+
+    #[error("Unsupported algorithm '{0}'")]
+    UnsupportedAlgorithm(String),
+}
+
+#[derive(Debug, Error)]
+pub enum ClipboardError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Clipboard command '{0}' failed")]
+    CommandFailed(String),
+
+    #[error("Clipboard lock poisoned")]
+    Poisoned,
 }
@@ -1,3 +1,4 @@
 pub mod ports;
 pub mod errors;
 pub mod models;
+pub mod secure_memory;
@@ -1,41 +1,423 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 use wincode::{SchemaRead, SchemaWrite};
 use zeroize::Zeroize;
 
+pub type EntryId = u64;
+
+// Previous passwords kept per entry
+pub const MAX_HISTORY: usize = 10;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, SchemaWrite, SchemaRead, Zeroize)]
 pub struct Entry {
+    pub id: EntryId,
     pub service: String,
     pub username: String,
     pub passwd: String,
+    pub urls: Vec<String>,
+    pub notes: Option<String>,
+    pub questions: Vec<SecurityQuestion>,
+    pub fields: Vec<CustomField>,
+    pub tags: Vec<String>,
+    pub folder: Option<String>,
+    pub totp: Option<TotpSecret>,
+    history: Vec<PasswordRecord>,
     created_at: i64,
     updated_at: i64,
 }
 
+// Searchable part of an entry, decrypted on unlock
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, SchemaWrite, SchemaRead, Zeroize)]
+pub struct EntryIndex {
+    pub id: EntryId,
+    pub service: String,
+    pub username: String,
+    pub urls: Vec<String>,
+    pub tags: Vec<String>,
+    pub folder: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+}
+
+// Secret part of an entry, encrypted under its own subkey
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, SchemaWrite, SchemaRead, Zeroize)]
+pub struct EntrySecrets {
+    passwd: String,
+    notes: Option<String>,
+    questions: Vec<SecurityQuestion>,
+    fields: Vec<CustomField>,
+    totp: Option<TotpSecret>,
+    history: Vec<PasswordRecord>,
+}
+
+// An entry as kept while unlocked, secrets only opened on demand
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, SchemaWrite, SchemaRead)]
+pub struct SealedEntry {
+    pub index: EntryIndex,
+    pub nonce: Vec<u8>,
+    pub cipher: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, SchemaWrite, SchemaRead, Zeroize)]
+pub struct PasswordRecord {
+    pub passwd: String,
+    pub replaced_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, SchemaWrite, SchemaRead, Zeroize)]
+pub struct SecurityQuestion {
+    pub question: String,
+    pub answer: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, SchemaWrite, SchemaRead, Zeroize)]
+pub struct CustomField {
+    pub name: String,
+    pub value: String,
+    pub secret: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, SchemaWrite, SchemaRead)]
+pub enum TotpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, SchemaWrite, SchemaRead, Zeroize)]
+pub struct TotpSecret {
+    pub secret: Vec<u8>,
+    #[zeroize(skip)]
+    pub algorithm: TotpAlgorithm,
+    pub digits: u32,
+    pub period: u64,
+}
+
+impl TotpSecret {
+    // RFC 6238 defaults
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self {
+            secret,
+            algorithm: TotpAlgorithm::Sha1,
+            digits: 6,
+            period: 30,
+        }
+    }
+}
+
 impl Entry {
-    pub fn new(service: String, username: String, passwd: String) -> Self {
+    pub fn new(id: EntryId, service: String, username: String, passwd: String) -> Self {
         let now = chrono::Utc::now().timestamp();
         Self {
+            id,
             service,
             username,
             passwd,
+            urls: Vec::new(),
+            notes: None,
+            questions: Vec::new(),
+            fields: Vec::new(),
+            tags: Vec::new(),
+            folder: None,
+            totp: None,
+            history: Vec::new(),
             created_at: now,
             updated_at: now,
         }
     }
+
+    // Rebuilds an entry read from an older vault layout
+    pub fn migrated(
+        id: EntryId,
+        service: String,
+        username: String,
+        passwd: String,
+        created_at: i64,
+        updated_at: i64,
+    ) -> Self {
+        Self {
+            created_at,
+            updated_at,
+            ..Self::new(id, service, username, passwd)
+        }
+    }
+
+    pub fn index(&self) -> EntryIndex {
+        EntryIndex {
+            id: self.id,
+            service: self.service.clone(),
+            username: self.username.clone(),
+            urls: self.urls.clone(),
+            tags: self.tags.clone(),
+            folder: self.folder.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+
+    pub fn secrets(&self) -> EntrySecrets {
+        EntrySecrets {
+            passwd: self.passwd.clone(),
+            notes: self.notes.clone(),
+            questions: self.questions.clone(),
+            fields: self.fields.clone(),
+            totp: self.totp.clone(),
+            history: self.history.clone(),
+        }
+    }
+
+    pub fn from_parts(index: EntryIndex, secrets: EntrySecrets) -> Self {
+        Self {
+            id: index.id,
+            service: index.service,
+            username: index.username,
+            passwd: secrets.passwd,
+            urls: index.urls,
+            notes: secrets.notes,
+            questions: secrets.questions,
+            fields: secrets.fields,
+            tags: index.tags,
+            folder: index.folder,
+            totp: secrets.totp,
+            history: secrets.history,
+            created_at: index.created_at,
+            updated_at: index.updated_at,
+        }
+    }
+
+    pub fn touch(&mut self) {
+        self.updated_at = chrono::Utc::now().timestamp();
+    }
+
+    // Most recent first
+    pub fn history(&self) -> &[PasswordRecord] {
+        &self.history
+    }
+
+    // Moves the current password into history, dropping the oldest record
+    pub fn set_password(&mut self, passwd: String) {
+        let old = std::mem::replace(&mut self.passwd, passwd);
+        self.history.insert(
+            0,
+            PasswordRecord {
+                passwd: old,
+                replaced_at: chrono::Utc::now().timestamp(),
+            },
+        );
+
+        if self.history.len() > MAX_HISTORY {
+            for mut record in self.history.drain(MAX_HISTORY..) {
+                record.zeroize();
+            }
+        }
+        self.touch();
+    }
+
+    // Index 0 is the most recent record
+    pub fn restore(&mut self, index: usize) -> bool {
+        if index >= self.history.len() {
+            return false;
+        }
+        let mut record = self.history.remove(index);
+        self.set_password(std::mem::take(&mut record.passwd));
+        true
+    }
+}
+
+// Fields left as None keep their current value
+#[derive(Default, Zeroize)]
+pub struct EntryUpdate {
+    pub service: Option<String>,
+    pub username: Option<String>,
+    pub passwd: Option<String>,
+    pub urls: Option<Vec<String>>,
+    pub notes: Option<Option<String>>,
+    pub questions: Option<Vec<SecurityQuestion>>,
+    pub fields: Option<Vec<CustomField>>,
+    pub tags: Option<Vec<String>>,
+    pub folder: Option<Option<String>>,
+    pub totp: Option<Option<TotpSecret>>,
+}
+
+#[derive(Default)]
+pub struct EntryFilter {
+    pub tag: Option<String>,
+    pub folder: Option<String>,
+}
+
+impl EntryIndex {
+    // A folder matches itself and all of its subfolders
+    pub fn in_folder(&self, folder: &str) -> bool {
+        let folder = folder.trim_matches('/');
+        self.folder.as_deref().is_some_and(|f| {
+            folder.is_empty()
+                || f == folder
+                || f.strip_prefix(folder).is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+}
+
+impl EntryFilter {
+    pub fn matches(&self, entry: &EntryIndex) -> bool {
+        self.tag.as_ref().is_none_or(|t| entry.tags.contains(t))
+            && self.folder.as_deref().is_none_or(|f| entry.in_folder(f))
+    }
+}
+
+impl EntryUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.service.is_none()
+            && self.username.is_none()
+            && self.passwd.is_none()
+            && self.urls.is_none()
+            && self.notes.is_none()
+            && self.questions.is_none()
+            && self.fields.is_none()
+            && self.tags.is_none()
+            && self.folder.is_none()
+            && self.totp.is_none()
+    }
+}
+
+// What happens to uncommitted changes when an idle vault is locked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdlePolicy {
+    Commit,
+    Discard,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, SchemaWrite, SchemaRead)]
+pub enum KdfAlgorithm {
+    Argon2d,
+    Argon2i,
+    Argon2id,
+}
+
+// Key derivation settings, stored in the vault header
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, SchemaWrite, SchemaRead)]
+pub struct KdfParams {
+    pub algorithm: KdfAlgorithm,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    // Same as argon2's defaults, which older vaults were created with
+    fn default() -> Self {
+        Self {
+            algorithm: KdfAlgorithm::Argon2id,
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl fmt::Display for KdfParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} m={}KiB t={} p={}",
+            self.algorithm, self.memory_kib, self.iterations, self.parallelism
+        )
+    }
 }
 
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, SchemaWrite, SchemaRead)]
+pub enum CipherSuite {
+    #[default]
+    Aes256Gcm,
+    XChaCha20Poly1305,
+}
+
+impl CipherSuite {
+    pub fn nonce_len(&self) -> usize {
+        match self {
+            CipherSuite::Aes256Gcm => 12,
+            CipherSuite::XChaCha20Poly1305 => 24,
+        }
+    }
+}
+
+impl fmt::Display for CipherSuite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CipherSuite::Aes256Gcm => write!(f, "aes256gcm"),
+            CipherSuite::XChaCha20Poly1305 => write!(f, "xchacha20poly1305"),
+        }
+    }
+}
+
+// Secrets a vault key is derived from
+#[derive(Clone, Copy)]
+pub struct Credentials<'a> {
+    pub password: &'a str,
+    pub keyfile: Option<&'a [u8]>,
+}
+
+impl<'a> Credentials<'a> {
+    pub fn new(password: &'a str, keyfile: Option<&'a [u8]>) -> Self {
+        Self { password, keyfile }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, SchemaWrite, SchemaRead)]
+pub enum SlotKind {
+    #[default]
+    Password,
+    Recovery,
+    Member,
+}
+
+impl fmt::Display for SlotKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlotKind::Password => write!(f, "password"),
+            SlotKind::Recovery => write!(f, "recovery"),
+            SlotKind::Member => write!(f, "member"),
+        }
+    }
+}
+
+// Team member a member slot is wrapped for
 #[derive(Serialize, Deserialize, Clone, SchemaWrite, SchemaRead)]
-pub struct VaultState {
+pub struct Member {
+    pub name: String,
+    // X25519 public key
+    pub public_key: [u8; 32],
+}
+
+// The data key wrapped under a key derived from one set of credentials,
+// or for a member's public key. Member slots leave the KDF fields unused
+#[derive(Serialize, Deserialize, Clone, SchemaWrite, SchemaRead)]
+pub struct KeySlot {
+    pub kind: SlotKind,
+    pub kdf: KdfParams,
     pub salt: [u8; 16],
-    pub nonce: [u8; 12],
+    // Whether a key file is mixed into key derivation
+    pub keyfile: bool,
+    pub member: Option<Member>,
+    // Nonce followed by the wrapped data key. Before v5 the derived key was
+    // the data key itself and this holds its key check instead
+    pub wrapped: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Clone, SchemaWrite, SchemaRead)]
+pub struct VaultState {
+    pub suite: CipherSuite,
+    pub slots: Vec<KeySlot>,
+    pub nonce: Vec<u8>,
     pub cipher: Vec<u8>,
 }
 
 impl VaultState {
-    pub fn new(salt: &[u8; 16]) -> Self {
+    pub fn new(suite: CipherSuite, slot: KeySlot) -> Self {
         Self {
-            salt: salt.clone(),
-            nonce: [0; 12],
+            suite,
+            slots: vec![slot],
+            nonce: vec![],
             cipher: vec![],
         }
     }
@@ -1,16 +1,53 @@
-use crate::domain::errors::{CryptoError, StorageError};
+use std::path::Path;
+
+use crate::domain::{
+    errors::{ClipboardError, CryptoError, StorageError},
+    models::{CipherSuite, Credentials, EntryId, KdfParams},
+    secure_memory::SecureBuffer,
+};
 
 pub trait CryptoPort {
+    fn select(&mut self, suite: CipherSuite) -> Result<(), CryptoError>;
     fn salt_gen(&self) -> [u8; 16];
-    fn init(&mut self, password: &str, salt: &[u8]) -> Result<(), CryptoError>;
-    fn encrypt(&self, plaintext: &[u8]) -> Result<(Vec<u8>, [u8; 12]), CryptoError>;
-    fn decrypt(&self, ciphertext: &[u8], nonce: &[u8]) -> Result<Vec<u8>, CryptoError>;
+    // Derives the data key straight from the credentials, as vaults before v5 did
+    fn init(&mut self, creds: &Credentials, salt: &[u8], kdf: &KdfParams) -> Result<(), CryptoError>;
+    // Replaces the data key with a random one
+    fn generate_key(&mut self) -> Result<(), CryptoError>;
+    // Data key encrypted under a key derived from the credentials, as nonce || ciphertext
+    fn wrap_key(&self, creds: &Credentials, salt: &[u8], kdf: &KdfParams) -> Result<Vec<u8>, CryptoError>;
+    // Makes a wrapped data key current, keeping the previous one on failure
+    fn unwrap_key(
+        &mut self,
+        wrapped: &[u8],
+        creds: &Credentials,
+        salt: &[u8],
+        kdf: &KdfParams,
+    ) -> Result<(), CryptoError>;
+    // Random X25519 key pair as (secret, public)
+    fn generate_identity(&self) -> ([u8; 32], [u8; 32]);
+    // Data key wrapped for a public key, as ephemeral public key || nonce || ciphertext
+    fn wrap_key_for(&self, public_key: &[u8; 32]) -> Result<Vec<u8>, CryptoError>;
+    // Makes a data key wrapped for our public key current, keeping the previous one on failure
+    fn unwrap_key_with(&mut self, wrapped: &[u8], secret: &[u8; 32]) -> Result<(), CryptoError>;
+    fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<(Vec<u8>, Vec<u8>), CryptoError>;
+    // Plaintext comes back in locked memory, zeroized on drop
+    fn decrypt(&self, ciphertext: &[u8], nonce: &[u8], aad: &[u8]) -> Result<SecureBuffer, CryptoError>;
+    // Entry secrets, under a subkey of the data key derived from the entry id
+    fn encrypt_entry(&self, id: EntryId, plaintext: &[u8]) -> Result<(Vec<u8>, Vec<u8>), CryptoError>;
+    fn decrypt_entry(&self, id: EntryId, ciphertext: &[u8], nonce: &[u8]) -> Result<SecureBuffer, CryptoError>;
+    fn clear(&mut self);
 }
 
 pub trait StoragePort {
     fn exists(&self) -> bool;
+    fn path(&self) -> &Path;
     fn set_path(&mut self, path: String);
     fn load(&self) -> Result<Vec<u8>, StorageError>;
     fn save(&self, data: &[u8]) -> Result<(), StorageError>;
     fn list_vaults(&self) -> Result<Vec<String>, StorageError>;
 }
+
+pub trait ClipboardPort {
+    fn set(&mut self, text: &str) -> Result<(), ClipboardError>;
+    fn clear(&mut self) -> Result<(), ClipboardError>;
+}
@@ -0,0 +1,270 @@
+use std::{
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    slice,
+};
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/* =======================
+   SECURE BUFFER
+======================= */
+
+// Fixed-size buffer for keys and serialized plaintext. On unix it is locked in
+// RAM, kept out of core dumps and surrounded by inaccessible guard pages; it is
+// zeroized before the memory is released everywhere
+pub struct SecureBuffer {
+    region: Region,
+    len: usize,
+}
+
+// The buffer owns its memory like a Vec does
+unsafe impl Send for SecureBuffer {}
+unsafe impl Sync for SecureBuffer {}
+
+impl SecureBuffer {
+    // Zero-filled buffer of `len` bytes
+    pub fn new(len: usize) -> Self {
+        Self {
+            region: Region::map(len),
+            len,
+        }
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut buffer = Self::new(bytes.len());
+        buffer.copy_from_slice(bytes);
+        buffer
+    }
+}
+
+impl Deref for SecureBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the region holds `len` initialized bytes for as long as self lives
+        unsafe { slice::from_raw_parts(self.region.data.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for SecureBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: as above, and &mut self makes the access exclusive
+        unsafe { slice::from_raw_parts_mut(self.region.data.as_ptr(), self.len) }
+    }
+}
+
+impl Zeroize for SecureBuffer {
+    fn zeroize(&mut self) {
+        self.deref_mut().zeroize();
+    }
+}
+
+impl Drop for SecureBuffer {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecureBuffer {}
+
+/* =======================
+   MEMORY REGION
+======================= */
+
+// Guard page | locked pages, data aligned to their end | guard page.
+// Overruns fault on the trailing guard instead of reading past the secret
+#[cfg(unix)]
+struct Region {
+    base: NonNull<u8>,
+    size: usize,
+    locked: usize,
+    data: NonNull<u8>,
+}
+
+#[cfg(unix)]
+impl Region {
+    fn map(len: usize) -> Self {
+        let page = page_size();
+        let inner = len.div_ceil(page) * page;
+        let size = inner + 2 * page;
+
+        // SAFETY: a fresh anonymous mapping, no existing memory is touched
+        let base = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if base == libc::MAP_FAILED {
+            panic!("Cannot map {} bytes of secure memory", size);
+        }
+
+        // SAFETY: every range below lies within the mapping made above
+        unsafe {
+            let inner_start = base.cast::<u8>().add(page);
+            let guarded = libc::mprotect(base, page, libc::PROT_NONE) == 0
+                && libc::mprotect(inner_start.add(inner).cast(), page, libc::PROT_NONE) == 0;
+            if !guarded {
+                libc::munmap(base, size);
+                panic!("Cannot protect the guard pages of secure memory");
+            }
+
+            #[cfg(target_os = "linux")]
+            libc::madvise(inner_start.cast(), inner, libc::MADV_DONTDUMP);
+
+            // Best effort, RLIMIT_MEMLOCK can be lower than what is asked for
+            let locked = if libc::mlock(inner_start.cast::<libc::c_void>(), inner) == 0 {
+                inner
+            } else {
+                0
+            };
+
+            Self {
+                base: NonNull::new_unchecked(base.cast()),
+                size,
+                locked,
+                data: NonNull::new_unchecked(inner_start.add(inner - len)),
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Region {
+    fn drop(&mut self) {
+        // SAFETY: base and size describe the mapping made in `map`
+        unsafe {
+            let inner_start = self.base.as_ptr().add(page_size());
+            if self.locked > 0 {
+                libc::munlock(inner_start.cast::<libc::c_void>(), self.locked);
+            }
+            libc::munmap(self.base.as_ptr().cast(), self.size);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    usize::try_from(size).unwrap_or(4096)
+}
+
+// Without mlock and mprotect only the zeroizing is left
+#[cfg(not(unix))]
+struct Region {
+    data: NonNull<u8>,
+    layout: std::alloc::Layout,
+}
+
+#[cfg(not(unix))]
+impl Region {
+    fn map(len: usize) -> Self {
+        let layout = std::alloc::Layout::array::<u8>(len.max(1)).expect("Buffer too large");
+        // SAFETY: the layout is never zero-sized
+        let data = unsafe { std::alloc::alloc_zeroed(layout) };
+        let Some(data) = NonNull::new(data) else {
+            std::alloc::handle_alloc_error(layout);
+        };
+        Self { data, layout }
+    }
+}
+
+#[cfg(not(unix))]
+impl Drop for Region {
+    fn drop(&mut self) {
+        // SAFETY: allocated in `map` with the same layout
+        unsafe { std::alloc::dealloc(self.data.as_ptr(), self.layout) }
+    }
+}
+
+/* =======================
+   CORE DUMPS
+======================= */
+
+// Keeps core dumps off while alive, restoring the previous limit and
+// dumpable flag on drop
+pub struct CoreDumpGuard {
+    #[cfg(unix)]
+    previous: Option<libc::rlimit>,
+    #[cfg(unix)]
+    dumpable: Option<libc::c_int>,
+}
+
+impl CoreDumpGuard {
+    #[cfg(unix)]
+    pub fn disable() -> Self {
+        let mut previous = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+
+        // SAFETY: both calls only read and write the rlimit structs passed in
+        let disabled = unsafe {
+            libc::getrlimit(libc::RLIMIT_CORE, &mut previous) == 0
+                && libc::setrlimit(
+                    libc::RLIMIT_CORE,
+                    &libc::rlimit {
+                        rlim_cur: 0,
+                        rlim_max: previous.rlim_max,
+                    },
+                ) == 0
+        };
+
+        // A process that was already undumpable stays so after the guard. Only
+        // 0 and 1 can be set again, a suid_dumpable 2 ends up as 0
+        let dumpable = get_dumpable();
+        set_dumpable(0);
+        Self {
+            previous: disabled.then_some(previous),
+            dumpable,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn disable() -> Self {
+        Self {}
+    }
+}
+
+impl Drop for CoreDumpGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            if let Some(previous) = &self.previous {
+                // SAFETY: as in `disable`
+                unsafe { libc::setrlimit(libc::RLIMIT_CORE, previous) };
+            }
+            if let Some(dumpable) = self.dumpable {
+                set_dumpable(dumpable);
+            }
+        }
+    }
+}
+
+// Linux also refuses ptrace attaches and /proc/<pid>/mem reads while undumpable
+#[cfg(target_os = "linux")]
+fn get_dumpable() -> Option<libc::c_int> {
+    // SAFETY: PR_GET_DUMPABLE only reads a flag of this process
+    let dumpable = unsafe { libc::prctl(libc::PR_GET_DUMPABLE) };
+    (dumpable >= 0).then_some(dumpable)
+}
+
+#[cfg(target_os = "linux")]
+fn set_dumpable(dumpable: libc::c_int) {
+    // SAFETY: PR_SET_DUMPABLE only changes a flag of this process
+    unsafe { libc::prctl(libc::PR_SET_DUMPABLE, dumpable as libc::c_ulong) };
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn get_dumpable() -> Option<libc::c_int> {
+    None
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn set_dumpable(_: libc::c_int) {}
@@ -1,5 +1,10 @@
+use std::process::ExitCode;
+
 use crate::{
-    adapters::{aes_crypto::AesGcmCrypto, cli::VaultCli, file_storage::FileStorage},
+    adapters::{
+        cli::VaultCli, clipboard::ClipboardTimer, file_storage::FileStorage,
+        oneshot::VaultOneShot, suite_crypto::SuiteCrypto,
+    },
     application::engine::VaultEngine,
 };
 
@@ -7,11 +12,18 @@ mod adapters;
 mod application;
 mod domain;
 
-fn main() -> anyhow::Result<()> {    
+fn main() -> anyhow::Result<ExitCode> {    
     let storage = FileStorage::new();
-    let crypto = AesGcmCrypto::new();
+    let crypto = SuiteCrypto::new();
     let engine = VaultEngine::new(storage, crypto);
 
-    let cli = VaultCli::new(engine);
-    cli?.run()
+    // Any argument switches to the non-interactive mode
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return Ok(VaultOneShot::new(engine).run(args));
+    }
+
+    let cli = VaultCli::new(engine, ClipboardTimer::from_env());
+    cli?.run()?;
+    Ok(ExitCode::SUCCESS)
 }